@@ -0,0 +1,137 @@
+use crate::error::Result;
+use std::collections::VecDeque;
+
+#[cfg(feature = "async")]
+use core::task::{Context as AsyncContext, Poll};
+#[cfg(feature = "async")]
+use futures_util::stream::{Stream, StreamExt};
+
+/// Turns the items of an iterator or stream into zero or more output items, see [`Adapter`].
+pub(crate) trait Process {
+    type Input;
+    type Output;
+
+    /// Processes `input`, pushing the items to yield in order to `out`.
+    fn process(&mut self, input: Self::Input, out: &mut VecDeque<Self::Output>);
+}
+
+/// The iterator or stream adapter behind wrappers like [`SequenceTracked`]. Every item of `inner`
+/// is passed to `processor` and the items it produces are yielded before the next item is taken
+/// from `inner`. Errors are passed through.
+///
+/// [`SequenceTracked`]: crate::SequenceTracked
+#[derive(Debug)]
+pub(crate) struct Adapter<I, P: Process> {
+    pub(crate) inner: I,
+    pub(crate) processor: P,
+    pending: VecDeque<P::Output>,
+}
+
+impl<I, P: Process> Adapter<I, P> {
+    #[inline]
+    pub(crate) fn new(inner: I, processor: P) -> Self {
+        Self {
+            inner,
+            processor,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<I, P> Adapter<I, P>
+where
+    I: Iterator<Item = Result<P::Input>>,
+    P: Process,
+{
+    pub(crate) fn next(&mut self) -> Option<Result<P::Output>> {
+        loop {
+            if let Some(output) = self.pending.pop_front() {
+                return Some(Ok(output));
+            }
+
+            match self.inner.next()? {
+                Ok(input) => self.processor.process(input, &mut self.pending),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S, P> Adapter<S, P>
+where
+    S: Stream<Item = Result<P::Input>> + Unpin,
+    P: Process,
+{
+    pub(crate) fn poll_next(
+        &mut self,
+        cx: &mut AsyncContext<'_>,
+    ) -> Poll<Option<Result<P::Output>>> {
+        loop {
+            if let Some(output) = self.pending.pop_front() {
+                return Poll::Ready(Some(Ok(output)));
+            }
+
+            match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(input))) => self.processor.process(input, &mut self.pending),
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{Adapter, Process};
+    use crate::{Error, Message, MonitorMessage, Received, SocketEvent};
+    use bitcoin::{hashes::Hash, BlockHash};
+    use std::collections::VecDeque;
+
+    /// A `hashblock` message with sequence number `seq`, of the block hash `[seq as u8; 32]`.
+    pub(crate) fn hashblock(seq: u32) -> Message {
+        Message::HashBlock(BlockHash::from_byte_array([seq as u8; 32]), seq)
+    }
+
+    pub(crate) fn received(endpoint: &str, message: Message) -> Received {
+        Received {
+            endpoint: endpoint.to_owned(),
+            message,
+        }
+    }
+
+    pub(crate) fn monitor_event(source_url: &str, event: SocketEvent) -> MonitorMessage {
+        MonitorMessage {
+            event,
+            source_url: source_url.to_owned(),
+        }
+    }
+
+    struct Repeat;
+
+    impl Process for Repeat {
+        type Input = u8;
+        type Output = u8;
+
+        fn process(&mut self, input: u8, out: &mut VecDeque<u8>) {
+            out.extend((0..input).map(|_| input));
+        }
+    }
+
+    #[test]
+    fn adapter() {
+        let items = [Ok(2), Ok(0), Err(Error::InvalidDataLength(0)), Ok(1)];
+        let mut adapter = Adapter::new(items.into_iter(), Repeat);
+
+        assert_eq!(adapter.next().unwrap().unwrap(), 2);
+        assert_eq!(adapter.next().unwrap().unwrap(), 2);
+        // 0 produces nothing, the error is passed through
+        assert!(matches!(
+            adapter.next().unwrap(),
+            Err(Error::InvalidDataLength(0))
+        ));
+        assert_eq!(adapter.next().unwrap().unwrap(), 1);
+        assert!(adapter.next().is_none());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::transaction::GhostTransaction;
+use bitcoin::{
+    block::Version,
+    consensus::{encode, Decodable, Encodable},
+    hash_types::WitnessMerkleNode,
+    hashes::Hash,
+    merkle_tree, BlockHash, CompactTarget, TxMerkleNode,
+};
+use std::io;
+
+/// Length of a serialized [`GhostBlockHeader`].
+pub const GHOST_HEADER_LEN: usize = 112;
+
+/// The header version of blocks that carry a [`GhostBlock::block_sig`]. Blocks with another
+/// version (like the genesis block) are serialized without it.
+pub const GHOST_BLOCK_VERSION: i32 = 0xa0000000u32 as i32;
+
+/// A block header as serialized by Ghost Core. Next to the fields Bitcoin has, it commits to the
+/// witness merkle root directly instead of through the coinbase.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GhostBlockHeader {
+    pub version: Version,
+    pub prev_blockhash: BlockHash,
+    pub merkle_root: TxMerkleNode,
+    pub witness_merkle_root: WitnessMerkleNode,
+    pub time: u32,
+    pub bits: CompactTarget,
+    pub nonce: u32,
+}
+
+/// A block as serialized by Ghost Core: a header, Ghost transactions and the signature of the
+/// staker (empty for proof-of-work blocks). Like the node, the signature is only serialized if the
+/// header version is [`GHOST_BLOCK_VERSION`], otherwise it is left out (and empty when decoding).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GhostBlock {
+    pub header: GhostBlockHeader,
+    pub txdata: Vec<GhostTransaction>,
+    pub block_sig: Vec<u8>,
+}
+
+impl GhostBlockHeader {
+    /// Returns true if the version of this header is [`GHOST_BLOCK_VERSION`], meaning the block
+    /// has a block signature.
+    #[inline]
+    pub fn is_ghost_version(&self) -> bool {
+        self.version.to_consensus() == GHOST_BLOCK_VERSION
+    }
+
+    /// Computes the hash of this header, the double SHA256 of all 112 bytes (including the
+    /// witness merkle root).
+    pub fn block_hash(&self) -> BlockHash {
+        let mut engine = BlockHash::engine();
+        self.consensus_encode(&mut engine)
+            .expect("engines don't error");
+        BlockHash::from_engine(engine)
+    }
+}
+
+impl Encodable for GhostBlockHeader {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.version.consensus_encode(w)?;
+        len += self.prev_blockhash.consensus_encode(w)?;
+        len += self.merkle_root.consensus_encode(w)?;
+        len += self.witness_merkle_root.consensus_encode(w)?;
+        len += self.time.consensus_encode(w)?;
+        len += self.bits.consensus_encode(w)?;
+        len += self.nonce.consensus_encode(w)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for GhostBlockHeader {
+    fn consensus_decode_from_finite_reader<R: io::Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(Self {
+            version: Decodable::consensus_decode_from_finite_reader(r)?,
+            prev_blockhash: Decodable::consensus_decode_from_finite_reader(r)?,
+            merkle_root: Decodable::consensus_decode_from_finite_reader(r)?,
+            witness_merkle_root: Decodable::consensus_decode_from_finite_reader(r)?,
+            time: Decodable::consensus_decode_from_finite_reader(r)?,
+            bits: Decodable::consensus_decode_from_finite_reader(r)?,
+            nonce: Decodable::consensus_decode_from_finite_reader(r)?,
+        })
+    }
+}
+
+impl GhostBlock {
+    /// Returns the hash of this block's header.
+    #[inline]
+    pub fn block_hash(&self) -> BlockHash {
+        self.header.block_hash()
+    }
+
+    /// Returns the coinstake transaction if this is a proof-of-stake block.
+    #[inline]
+    pub fn coinstake(&self) -> Option<&GhostTransaction> {
+        self.txdata.iter().find(|tx| tx.is_coinstake())
+    }
+
+    /// Returns the coinbase transaction if this is a proof-of-work block.
+    #[inline]
+    pub fn coinbase(&self) -> Option<&GhostTransaction> {
+        self.txdata.first().filter(|tx| tx.is_coinbase())
+    }
+
+    /// Returns the height of this block as stored in its coinbase or coinstake transaction, see
+    /// [`GhostTransaction::height`].
+    #[inline]
+    pub fn height(&self) -> Option<u32> {
+        self.txdata.first()?.height()
+    }
+
+    /// Returns true if this block is a proof-of-stake block.
+    #[inline]
+    pub fn is_proof_of_stake(&self) -> bool {
+        self.coinstake().is_some()
+    }
+
+    /// Computes the merkle root of the txids of this block's transactions.
+    pub fn compute_merkle_root(&self) -> Option<TxMerkleNode> {
+        let hashes = self
+            .txdata
+            .iter()
+            .map(|tx| TxMerkleNode::from_raw_hash(tx.txid().to_raw_hash()));
+        merkle_tree::calculate_root(hashes)
+    }
+
+    /// Computes the merkle root of the wtxids of this block's transactions. Unlike Bitcoin, Ghost
+    /// uses the wtxid of the first transaction too.
+    pub fn compute_witness_merkle_root(&self) -> Option<WitnessMerkleNode> {
+        let hashes = self
+            .txdata
+            .iter()
+            .map(|tx| WitnessMerkleNode::from_raw_hash(tx.wtxid().to_raw_hash()));
+        merkle_tree::calculate_root(hashes)
+    }
+
+    /// Returns true if both merkle roots in the header match the transactions in this block.
+    pub fn check_merkle_roots(&self) -> bool {
+        self.compute_merkle_root() == Some(self.header.merkle_root)
+            && self.compute_witness_merkle_root() == Some(self.header.witness_merkle_root)
+    }
+}
+
+impl Encodable for GhostBlock {
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.header.consensus_encode(w)?;
+        len += encode::VarInt(self.txdata.len() as u64).consensus_encode(w)?;
+        for tx in &self.txdata {
+            len += tx.consensus_encode(w)?;
+        }
+        if self.header.is_ghost_version() {
+            len += self.block_sig.consensus_encode(w)?;
+        }
+        Ok(len)
+    }
+}
+
+impl Decodable for GhostBlock {
+    fn consensus_decode_from_finite_reader<R: io::Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let header: GhostBlockHeader = Decodable::consensus_decode_from_finite_reader(r)?;
+
+        let tx_len = encode::VarInt::consensus_decode_from_finite_reader(r)?.0;
+        let mut txdata = Vec::new();
+        for _ in 0..tx_len {
+            txdata.push(GhostTransaction::consensus_decode_from_finite_reader(r)?);
+        }
+
+        let block_sig = if header.is_ghost_version() {
+            Decodable::consensus_decode_from_finite_reader(r)?
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            header,
+            txdata,
+            block_sig,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{GhostBlock, GhostBlockHeader, GHOST_BLOCK_VERSION, GHOST_HEADER_LEN};
+    use crate::transaction::tests::sample_transaction;
+    use bitcoin::{
+        block::Version,
+        consensus::{deserialize, serialize},
+        hash_types::WitnessMerkleNode,
+        hashes::{sha256d, Hash},
+        BlockHash, CompactTarget, TxMerkleNode,
+    };
+
+    /// A proof-of-stake block containing the sample transaction with valid merkle roots.
+    pub(crate) fn sample_block() -> GhostBlock {
+        let mut block = GhostBlock {
+            header: GhostBlockHeader {
+                version: Version::from_consensus(GHOST_BLOCK_VERSION),
+                prev_blockhash: BlockHash::from_byte_array([1; 32]),
+                merkle_root: TxMerkleNode::all_zeros(),
+                witness_merkle_root: WitnessMerkleNode::all_zeros(),
+                time: 1_700_000_000,
+                bits: CompactTarget::from_consensus(0x1e0fffff),
+                nonce: 0,
+            },
+            txdata: vec![sample_transaction()],
+            block_sig: vec![0x30; 71],
+        };
+        block.header.merkle_root = block.compute_merkle_root().unwrap();
+        block.header.witness_merkle_root = block.compute_witness_merkle_root().unwrap();
+        block
+    }
+
+    #[test]
+    fn serialization() {
+        let block = sample_block();
+
+        let bytes = serialize(&block);
+        let header_bytes = serialize(&block.header);
+        assert_eq!(header_bytes.len(), GHOST_HEADER_LEN);
+        assert_eq!(&bytes[..GHOST_HEADER_LEN], header_bytes);
+        assert_eq!(
+            &bytes[bytes.len() - 72..],
+            &[&[71u8] as &[u8], &[0x30; 71]].concat()
+        );
+        assert_eq!(deserialize::<GhostBlock>(&bytes).unwrap(), block);
+
+        assert_eq!(
+            block.block_hash().to_byte_array(),
+            sha256d::Hash::hash(&header_bytes).to_byte_array()
+        );
+        assert!(block.is_proof_of_stake());
+        assert_eq!(block.height(), Some(100));
+        assert!(block.coinbase().is_none());
+        assert!(block.check_merkle_roots());
+
+        // missing block signature
+        assert!(deserialize::<GhostBlock>(&bytes[..bytes.len() - 72]).is_err());
+    }
+
+    #[test]
+    fn non_ghost_version() {
+        let mut block = sample_block();
+        let ghost_bytes = serialize(&block);
+
+        block.header.version = Version::from_consensus(4);
+        assert!(!block.header.is_ghost_version());
+
+        // the block signature is not serialized
+        let bytes = serialize(&block);
+        assert_eq!(bytes.len(), ghost_bytes.len() - 72);
+        assert_eq!(
+            &bytes[GHOST_HEADER_LEN..],
+            &ghost_bytes[GHOST_HEADER_LEN..bytes.len()]
+        );
+
+        block.block_sig.clear();
+        assert_eq!(deserialize::<GhostBlock>(&bytes).unwrap(), block);
+    }
+}
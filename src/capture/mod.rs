@@ -0,0 +1,382 @@
+pub mod recorder;
+pub mod replay;
+pub mod tap;
+
+use crate::{error::Result, message::Message, received::Received};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The first bytes of a capture file.
+pub const CAPTURE_MAGIC: [u8; 8] = *b"GZMQCAP\0";
+
+/// The version of the capture file format written by [`CaptureWriter`].
+pub const CAPTURE_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = CAPTURE_MAGIC.len() + 2;
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A multipart message as received from a publisher, together with the endpoint it was received
+/// from and when. The parts are stored as is, so messages that can not be decoded are recorded as
+/// well.
+///
+/// A record is stored as the timestamp in microseconds since the Unix epoch (u64), the length of
+/// the endpoint (u16) followed by the endpoint, the number of parts (u32) and every part as its
+/// length (u32) followed by its bytes. All integers are little-endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    pub timestamp: SystemTime,
+    pub endpoint: String,
+    pub parts: Vec<Vec<u8>>,
+}
+
+impl CaptureRecord {
+    /// Decodes the parts into a [`Message`], returning the same error a subscriber would have
+    /// returned when receiving them.
+    #[inline]
+    pub fn to_message(&self) -> Result<Message> {
+        Message::from_multipart(&self.parts)
+    }
+
+    /// Decodes the parts into a [`Message`] tagged with the endpoint it was received from.
+    #[inline]
+    pub fn to_received(&self) -> Result<Received> {
+        Ok(Received {
+            endpoint: self.endpoint.clone(),
+            message: self.to_message()?,
+        })
+    }
+
+    /// Writes this record to `writer`.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros();
+        let micros = u64::try_from(micros).unwrap_or(u64::MAX);
+        let endpoint_len = u16::try_from(self.endpoint.len())
+            .map_err(|_| invalid_data(format!("endpoint too long: {}", self.endpoint.len())))?;
+        let parts_len = u32::try_from(self.parts.len())
+            .map_err(|_| invalid_data(format!("too many parts: {}", self.parts.len())))?;
+
+        writer.write_all(&micros.to_le_bytes())?;
+        writer.write_all(&endpoint_len.to_le_bytes())?;
+        writer.write_all(self.endpoint.as_bytes())?;
+        writer.write_all(&parts_len.to_le_bytes())?;
+        for part in &self.parts {
+            let len = u32::try_from(part.len())
+                .map_err(|_| invalid_data(format!("part too long: {}", part.len())))?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(part)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a record from `reader`, returning [`None`] at the end of the input. A record that is
+    /// cut off, even within its timestamp, is an [`io::ErrorKind::UnexpectedEof`] error.
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Option<Self>> {
+        let mut micros = [0; 8];
+        let mut read = 0;
+        while read < micros.len() {
+            match reader.read(&mut micros[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => read += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let timestamp = UNIX_EPOCH + Duration::from_micros(u64::from_le_bytes(micros));
+
+        let mut endpoint_len = [0; 2];
+        reader.read_exact(&mut endpoint_len)?;
+        let mut endpoint = vec![0; u16::from_le_bytes(endpoint_len).into()];
+        reader.read_exact(&mut endpoint)?;
+        let endpoint = String::from_utf8(endpoint)
+            .map_err(|_| invalid_data("endpoint is not valid UTF-8".to_owned()))?;
+
+        let mut parts_len = [0; 4];
+        reader.read_exact(&mut parts_len)?;
+        let parts_len = u32::from_le_bytes(parts_len);
+
+        let mut parts = Vec::new();
+        for _ in 0..parts_len {
+            let mut len = [0; 4];
+            reader.read_exact(&mut len)?;
+            let len = u32::from_le_bytes(len) as usize;
+
+            let mut part = Vec::new();
+            // don't trust the length to allocate, a truncated file would allocate for nothing
+            reader.by_ref().take(len as u64).read_to_end(&mut part)?;
+            if part.len() != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            parts.push(part);
+        }
+
+        Ok(Some(Self {
+            timestamp,
+            endpoint,
+            parts,
+        }))
+    }
+}
+
+/// Writes [`CaptureRecord`]s to a capture file.
+#[derive(Debug)]
+pub struct CaptureWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Writes the header to `writer` and returns a [`CaptureWriter`] that writes records to it.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(&CAPTURE_MAGIC)?;
+        writer.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// Returns a [`CaptureWriter`] that appends records to `writer`, which is positioned after
+    /// the header and records of an existing capture.
+    #[inline]
+    pub fn append(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes a record and flushes the writer, so the record is stored even if the process
+    /// crashes afterwards.
+    pub fn write(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        record.write_to(&mut self.writer)?;
+        self.writer.flush()
+    }
+
+    /// Returns the underlying writer.
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl CaptureWriter<BufWriter<File>> {
+    /// Opens the capture file at `path` to append records to, creating it if it does not exist. If
+    /// the file exists, its header and records are checked. A record that was cut off at the end
+    /// of the file (because the process writing it crashed) is removed, so the records appended
+    /// after it can be read.
+    pub fn open_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)?;
+
+        if file.seek(SeekFrom::End(0))? == 0 {
+            return Self::new(BufWriter::new(file));
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        let mut reader = BufReader::new(&file);
+        read_header(&mut reader)?;
+
+        let end = loop {
+            let start = reader.stream_position()?;
+
+            match CaptureRecord::read_from(&mut reader) {
+                Ok(Some(_)) => {}
+                Ok(None) => break None,
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break Some(start),
+                Err(err) => return Err(err),
+            }
+        };
+
+        if let Some(end) = end {
+            file.set_len(end)?;
+        }
+
+        Ok(Self::append(BufWriter::new(file)))
+    }
+}
+
+fn read_header<R: Read>(reader: &mut R) -> io::Result<()> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header)?;
+
+    if header[..CAPTURE_MAGIC.len()] != CAPTURE_MAGIC {
+        return Err(invalid_data("not a capture file".to_owned()));
+    }
+
+    let version = u16::from_le_bytes([header[HEADER_LEN - 2], header[HEADER_LEN - 1]]);
+    if version != CAPTURE_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported capture file version {version} (expected {CAPTURE_VERSION})"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Reads the [`CaptureRecord`]s of a capture file, in the order they were written.
+#[derive(Debug)]
+pub struct CaptureReader<R> {
+    reader: R,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Reads and checks the header from `reader` and returns a [`CaptureReader`] that reads the
+    /// records following it.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        read_header(&mut reader)?;
+        Ok(Self { reader })
+    }
+
+    /// Reads the next record, returning [`None`] at the end of the file.
+    #[inline]
+    pub fn read(&mut self) -> io::Result<Option<CaptureRecord>> {
+        CaptureRecord::read_from(&mut self.reader)
+    }
+}
+
+impl CaptureReader<BufReader<File>> {
+    /// Opens the capture file at `path`.
+    #[inline]
+    pub fn open_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = io::Result<CaptureRecord>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read().transpose()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{CaptureReader, CaptureRecord, CaptureWriter, CAPTURE_MAGIC};
+    use crate::{Error, Message};
+    use bitcoin::{hashes::Hash, BlockHash};
+    use std::{
+        env, fs,
+        io::{self, Cursor, Write},
+        process,
+        sync::mpsc::Sender,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    /// A writer that sends everything written to it to a channel, to read a capture while it is
+    /// owned by a recorder or tap.
+    pub(crate) struct ChannelWriter(pub(crate) Sender<Vec<u8>>);
+
+    impl Write for ChannelWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.send(buf.to_vec()).unwrap();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn sample_records() -> Vec<CaptureRecord> {
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 3);
+
+        vec![
+            CaptureRecord {
+                timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_000),
+                endpoint: "tcp://127.0.0.1:28332".to_owned(),
+                parts: msg.serialize_to_vecs().to_vec(),
+            },
+            CaptureRecord {
+                timestamp: UNIX_EPOCH + Duration::from_micros(1_700_000_000_250_000),
+                endpoint: "tcp://127.0.0.1:28332".to_owned(),
+                parts: vec![b"rawblock".to_vec(), vec![0xff; 10], vec![0; 4]],
+            },
+        ]
+    }
+
+    #[test]
+    fn round_trip() {
+        let records = sample_records();
+
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        let bytes = writer.into_inner();
+        assert_eq!(bytes[..8], CAPTURE_MAGIC);
+
+        let read = CaptureReader::new(Cursor::new(&bytes))
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, records);
+
+        assert_eq!(
+            read[0].to_message().unwrap(),
+            Message::HashBlock(BlockHash::all_zeros(), 3)
+        );
+        assert!(matches!(
+            read[1].to_message(),
+            Err(Error::BitcoinDeserialization(_))
+        ));
+
+        // a truncated record is an error, not the end of the file
+        let mut reader = CaptureReader::new(Cursor::new(&bytes[..bytes.len() - 1])).unwrap();
+        assert!(reader.read().unwrap().is_some());
+        assert!(reader.read().is_err());
+
+        // even within the timestamp
+        let mut reader = CaptureReader::new(Cursor::new(&bytes[..10 + 3])).unwrap();
+        assert_eq!(
+            reader.read().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut bytes = bytes;
+        bytes[8] = 2;
+        assert!(CaptureReader::new(Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn open_file_truncated() {
+        let path = env::temp_dir().join(format!("ghostcore-zmq-truncated-{}.gzmq", process::id()));
+        let records = sample_records();
+
+        let mut writer = CaptureWriter::open_file(&path).unwrap();
+        for record in &records {
+            writer.write(record).unwrap();
+        }
+        drop(writer);
+
+        // cut off the last record, like a crash while writing it
+        let len = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 5)
+            .unwrap();
+
+        let mut writer = CaptureWriter::open_file(&path).unwrap();
+        writer.write(&records[1]).unwrap();
+        drop(writer);
+
+        let read = CaptureReader::open_file(&path)
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.unwrap(), records);
+    }
+}
@@ -0,0 +1,162 @@
+use super::{tap::CaptureTap, CaptureWriter};
+use crate::{
+    error::{Error, Result},
+    raw_message::RawMessage,
+    subscribe::{builder::SubscriberBuilder, handle::SubscriptionHandle, poll_internal},
+};
+use core::ops::ControlFlow;
+use std::io::{self, Write};
+use zmq::{Context, Socket};
+
+/// Records every multipart message published on a set of endpoints to a capture file, see
+/// [`CaptureWriter`]. Messages are recorded as received, before they are decoded, so a message
+/// that produced an error (like [`Error::BitcoinDeserialization`]) can be reproduced offline
+/// byte for byte.
+///
+/// The recorder uses its own sockets, which receive the same messages as any other subscriber on
+/// the same endpoints, and records on a background thread until [`Recorder::stop`] is called. To
+/// record the messages of an existing subscription instead, add a [`CaptureTap`] to it.
+///
+/// ```no_run
+/// use ghostcore_zmq::{subscribe_receiver, CaptureWriter, Recorder};
+///
+/// let endpoints = ["tcp://127.0.0.1:28332"];
+///
+/// let recorder = Recorder::start(
+///     &endpoints,
+///     CaptureWriter::open_file("capture.gzmq").unwrap(),
+/// )
+/// .unwrap();
+///
+/// for msg in subscribe_receiver(&endpoints).unwrap() {
+///     if let Err(err) = msg {
+///         println!("Error receiving message: {err}, stopping");
+///         break;
+///     }
+/// }
+///
+/// let recorded = recorder.stop().unwrap();
+/// println!("recorded {recorded} messages");
+/// ```
+///
+/// [`Error::BitcoinDeserialization`]: crate::Error::BitcoinDeserialization
+pub struct Recorder {
+    handle: SubscriptionHandle,
+    tap: CaptureTap,
+}
+
+impl Recorder {
+    /// Subscribes to all topics on `endpoints` and records every message to `writer`. Use
+    /// [`SubscriberBuilder::subscribe_recorder`] to configure the sockets.
+    #[inline]
+    pub fn start<W>(endpoints: &[&str], writer: CaptureWriter<W>) -> Result<Self>
+    where
+        W: Write + Send + 'static,
+    {
+        SubscriberBuilder::new(endpoints).subscribe_recorder(writer)
+    }
+
+    pub(crate) fn spawn(
+        context: &Context,
+        sockets: Vec<(String, Socket)>,
+        tap: CaptureTap,
+    ) -> Result<Self> {
+        let thread_tap = tap.clone();
+
+        let handle = SubscriptionHandle::spawn(context, move |control| {
+            let tap = thread_tap;
+
+            // the messages are only checked, decoding errors are recorded like any other message
+            let _ = poll_internal::<RawMessage, _, _>(
+                &sockets,
+                Some(&control),
+                Some(&tap),
+                -1,
+                |msg| match msg {
+                    Some(Err(Error::Zmq(err))) => {
+                        tap.fail(io::Error::other(err));
+                        ControlFlow::Break(())
+                    }
+                    _ if tap.has_failed() => ControlFlow::Break(()),
+                    _ => ControlFlow::Continue(()),
+                },
+            );
+        })?;
+
+        Ok(Self { handle, tap })
+    }
+
+    /// Returns the number of messages recorded so far.
+    #[inline]
+    pub fn recorded(&self) -> u64 {
+        self.tap.recorded()
+    }
+
+    /// Returns true if recording stopped because of an error, which is returned by
+    /// [`Recorder::stop`].
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Stops recording and returns the number of messages recorded, or the error that stopped
+    /// recording before.
+    pub fn stop(self) -> io::Result<u64> {
+        let Self { handle, tap } = self;
+
+        handle.shutdown_and_join().map_err(io::Error::other)?;
+
+        match tap.take_error() {
+            Some(err) => Err(err),
+            None => Ok(tap.recorded()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        capture::tests::ChannelWriter, testing::MockPublisher, CaptureReader, CaptureWriter,
+        Message,
+    };
+    use bitcoin::{hashes::Hash, BlockHash};
+    use core::time::Duration;
+    use std::{io::Cursor, sync::mpsc::channel, thread};
+
+    #[test]
+    fn record() {
+        let mut publisher = MockPublisher::inproc().unwrap();
+
+        let (tx, rx) = channel();
+        let recorder = publisher
+            .subscriber()
+            .subscribe_recorder(CaptureWriter::new(ChannelWriter(tx)).unwrap())
+            .unwrap();
+
+        assert!(publisher
+            .wait_for_subscription(Duration::from_secs(10))
+            .unwrap());
+
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        publisher.send(&msg).unwrap();
+        publisher
+            .send_raw(&[b"hashblock".as_slice(), b"\x00"])
+            .unwrap();
+
+        while recorder.recorded() < 2 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(recorder.stop().unwrap(), 2);
+
+        let bytes: Vec<u8> = rx.try_iter().flatten().collect();
+        let records: Vec<_> = CaptureReader::new(Cursor::new(bytes))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].endpoint, publisher.endpoint());
+        assert_eq!(records[0].to_message().unwrap(), msg);
+        assert_eq!(records[1].parts, [b"hashblock".to_vec(), vec![0]]);
+    }
+}
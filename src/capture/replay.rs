@@ -0,0 +1,341 @@
+use super::{CaptureReader, CaptureRecord};
+use crate::{error::Result, message::Message, publish::Publisher};
+use core::{ops::ControlFlow, time::Duration};
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    sync::mpsc::{channel, Receiver},
+    thread,
+    time::{Instant, SystemTime},
+};
+
+#[cfg(feature = "async")]
+use crate::subscribe::stream::{sleep, Sleep};
+#[cfg(feature = "async")]
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context as AsyncContext, Poll},
+};
+#[cfg(feature = "async")]
+use futures_util::stream::Stream;
+
+/// How fast a [`Replayer`] replays a capture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// With the same delays between messages as when they were recorded.
+    Original,
+    /// Like [`ReplaySpeed::Original`], but the given number of times faster (2.0 is twice as
+    /// fast, 0.5 half as fast). Values that are not positive and finite replay at
+    /// [`ReplaySpeed::Maximum`].
+    Scaled(f64),
+    /// Without any delays.
+    Maximum,
+}
+
+/// Replays a capture file written by a [`Recorder`][crate::Recorder], producing the messages the
+/// same way the live subscribers do: as an [`Iterator`] (and a [`Stream`] with the `async`
+/// feature), with a callback ([`Replayer::replay_blocking`]) or a [`Receiver`]
+/// ([`Replayer::replay_receiver`]). Messages that could not be decoded when they were recorded
+/// produce the same error again. Errors reading the file are returned as [`Error::Io`] and end the
+/// replay.
+///
+/// [`Replayer::publish`] republishes the messages on a socket instead, so the replay can be
+/// received by any subscriber.
+///
+/// ```no_run
+/// use ghostcore_zmq::{ReplaySpeed, Replayer};
+///
+/// let replayer = Replayer::open_file("capture.gzmq")
+///     .unwrap()
+///     .speed(ReplaySpeed::Scaled(10.0));
+///
+/// for msg in replayer {
+///     match msg {
+///         Ok(msg) => println!("Replayed message: {msg}"),
+///         Err(err) => println!("Error replaying message: {err}"),
+///     }
+/// }
+/// ```
+///
+/// [`Stream`]: futures_util::stream::Stream
+/// [`Error::Io`]: crate::Error::Io
+pub struct Replayer<R> {
+    reader: Option<CaptureReader<R>>,
+    speed: ReplaySpeed,
+    origin: Option<(SystemTime, Instant)>,
+    #[cfg(feature = "async")]
+    pending: Option<(CaptureRecord, Sleep)>,
+}
+
+impl Replayer<BufReader<File>> {
+    /// Opens the capture file at `path` to replay at maximum speed.
+    #[inline]
+    pub fn open_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new(CaptureReader::open_file(path)?))
+    }
+}
+
+impl<R: Read> Replayer<R> {
+    /// Creates a new [`Replayer`] that replays the records of `reader` at maximum speed.
+    #[inline]
+    pub fn new(reader: CaptureReader<R>) -> Self {
+        Self {
+            reader: Some(reader),
+            speed: ReplaySpeed::Maximum,
+            origin: None,
+            #[cfg(feature = "async")]
+            pending: None,
+        }
+    }
+
+    /// Sets the replay speed.
+    #[inline]
+    pub fn speed(mut self, speed: ReplaySpeed) -> Self {
+        self.speed = match speed {
+            ReplaySpeed::Scaled(scale) if !(scale.is_finite() && scale > 0.0) => {
+                ReplaySpeed::Maximum
+            }
+            speed => speed,
+        };
+        self
+    }
+
+    /// Returns how long to wait before replaying `record`.
+    fn delay(&mut self, record: &CaptureRecord) -> Duration {
+        let scale = match self.speed {
+            ReplaySpeed::Original => 1.0,
+            ReplaySpeed::Scaled(scale) => scale,
+            ReplaySpeed::Maximum => return Duration::ZERO,
+        };
+
+        let (origin_timestamp, origin) = *self
+            .origin
+            .get_or_insert_with(|| (record.timestamp, Instant::now()));
+
+        let elapsed = record
+            .timestamp
+            .duration_since(origin_timestamp)
+            .unwrap_or_default();
+        // a tiny scale can make the delay too long to represent, don't wait at all then
+        Duration::try_from_secs_f64(elapsed.as_secs_f64() / scale)
+            .ok()
+            .and_then(|delay| origin.checked_add(delay))
+            .map_or(Duration::ZERO, |due| {
+                due.saturating_duration_since(Instant::now())
+            })
+    }
+
+    /// Reads the next record, returning [`None`] at the end of the capture or after an error.
+    fn read(&mut self) -> Option<Result<CaptureRecord>> {
+        let res = self.reader.as_mut()?.read().transpose()?;
+        if res.is_err() {
+            self.reader = None;
+        }
+        Some(res.map_err(Into::into))
+    }
+
+    /// Waits until the next record is due and returns it, or [`None`] at the end of the capture.
+    pub fn next_record(&mut self) -> Option<Result<CaptureRecord>> {
+        let record = match self.read()? {
+            Ok(record) => record,
+            Err(err) => return Some(Err(err)),
+        };
+
+        thread::sleep(self.delay(&record));
+
+        Some(Ok(record))
+    }
+
+    /// Replays the capture and blocks the thread until it is done or [`ControlFlow::Break`] is
+    /// returned by the callback.
+    pub fn replay_blocking<F, B>(mut self, mut callback: F) -> ControlFlow<B>
+    where
+        F: FnMut(Result<Message>) -> ControlFlow<B>,
+    {
+        for msg in &mut self {
+            callback(msg)?;
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Replays the capture on a background thread and returns a [`Receiver`] of its messages. The
+    /// receiver is disconnected when the replay is done.
+    pub fn replay_receiver(self) -> Receiver<Result<Message>>
+    where
+        R: Send + 'static,
+    {
+        let (tx, rx) = channel();
+
+        thread::spawn(move || {
+            self.replay_blocking(|msg| {
+                if tx.send(msg).is_err() {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+        });
+
+        rx
+    }
+
+    /// Replays the capture by sending every record as is on the socket of `publisher`, keeping the
+    /// sequence numbers they were recorded with. Returns the number of messages sent.
+    ///
+    /// Subscribers only receive messages sent after their subscription reached the publisher,
+    /// see [`MockPublisher::wait_for_subscription`][crate::testing::MockPublisher::wait_for_subscription]
+    /// for a way to wait for that.
+    pub fn publish(mut self, publisher: &Publisher) -> Result<u64> {
+        let mut sent = 0;
+
+        while let Some(record) = self.next_record() {
+            publisher.as_zmq_socket().send_multipart(record?.parts, 0)?;
+            sent += 1;
+        }
+
+        Ok(sent)
+    }
+}
+
+impl<R: Read> Iterator for Replayer<R> {
+    type Item = Result<Message>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_record()?.and_then(|record| record.to_message()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: Read + Unpin> Stream for Replayer<R> {
+    type Item = Result<Message>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut AsyncContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+
+        if this.pending.is_none() {
+            let record = match this.read() {
+                Some(Ok(record)) => record,
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                None => return Poll::Ready(None),
+            };
+
+            let delay = this.delay(&record);
+            if delay.is_zero() {
+                return Poll::Ready(Some(record.to_message()));
+            }
+
+            this.pending = Some((record, sleep(delay)));
+        }
+
+        let (_, sleep) = this.pending.as_mut().unwrap();
+        match Pin::new(sleep).poll(cx) {
+            Poll::Ready(()) => {
+                let (record, _) = this.pending.take().unwrap();
+                Poll::Ready(Some(record.to_message()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        capture::tests::sample_records, testing::MockPublisher, CaptureReader, CaptureWriter,
+        Error, ReplaySpeed, Replayer,
+    };
+    use core::{ops::ControlFlow, time::Duration};
+    use std::{io::Cursor, time::Instant};
+
+    fn capture() -> Vec<u8> {
+        let mut writer = CaptureWriter::new(Vec::new()).unwrap();
+        for record in sample_records() {
+            writer.write(&record).unwrap();
+        }
+        writer.into_inner()
+    }
+
+    fn replayer(bytes: &[u8]) -> Replayer<Cursor<&[u8]>> {
+        Replayer::new(CaptureReader::new(Cursor::new(bytes)).unwrap())
+    }
+
+    #[test]
+    fn replay() {
+        let bytes = capture();
+        let records = sample_records();
+
+        let messages: Vec<_> = replayer(&bytes).collect();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0].as_ref().unwrap(),
+            &records[0].to_message().unwrap()
+        );
+        assert!(matches!(messages[1], Err(Error::BitcoinDeserialization(_))));
+
+        // the records are 250ms apart
+        let start = Instant::now();
+        let result = replayer(&bytes)
+            .speed(ReplaySpeed::Scaled(10.0))
+            .replay_blocking(|msg| match msg {
+                Ok(_) => ControlFlow::Continue(()),
+                Err(err) => ControlFlow::Break(err),
+            });
+        assert!(matches!(
+            result,
+            ControlFlow::Break(Error::BitcoinDeserialization(_))
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(25));
+
+        // a truncated capture ends with an error
+        let truncated = bytes[..bytes.len() - 1].to_vec();
+        let rx =
+            Replayer::new(CaptureReader::new(Cursor::new(truncated)).unwrap()).replay_receiver();
+        assert!(rx.recv().unwrap().is_ok());
+        assert!(matches!(rx.recv().unwrap(), Err(Error::Io(_))));
+        assert!(rx.recv().is_err());
+    }
+
+    #[test]
+    fn invalid_speed() {
+        let bytes = capture();
+
+        for scale in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let replayer = replayer(&bytes).speed(ReplaySpeed::Scaled(scale));
+            assert_eq!(replayer.speed, ReplaySpeed::Maximum);
+            assert_eq!(replayer.count(), 2);
+        }
+
+        // the delay is too long to represent
+        let start = Instant::now();
+        assert_eq!(
+            replayer(&bytes).speed(ReplaySpeed::Scaled(1e-300)).count(),
+            2
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn publish() {
+        let bytes = capture();
+
+        let mut publisher = MockPublisher::inproc().unwrap();
+        let rx = publisher.subscriber().subscribe_receiver().unwrap();
+        assert!(publisher
+            .wait_for_subscription(Duration::from_secs(10))
+            .unwrap());
+
+        assert_eq!(replayer(&bytes).publish(publisher.publisher()).unwrap(), 2);
+
+        assert_eq!(
+            rx.recv().unwrap().unwrap(),
+            sample_records()[0].to_message().unwrap()
+        );
+        assert!(matches!(
+            rx.recv().unwrap(),
+            Err(Error::BitcoinDeserialization(_))
+        ));
+    }
+}
@@ -0,0 +1,241 @@
+use super::{CaptureRecord, CaptureWriter};
+use std::{
+    fmt,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::SystemTime,
+};
+
+#[derive(Default)]
+struct Shared {
+    writer: Mutex<Option<CaptureWriter<Box<dyn Write + Send>>>>,
+    recorded: AtomicU64,
+    error: Mutex<Option<io::Error>>,
+}
+
+/// Records the messages received by a subscription to a capture file, see [`CaptureWriter`].
+/// Messages are recorded as received, before they are decoded, so a message that produced an
+/// error (like [`Error::BitcoinDeserialization`]) can be reproduced offline byte for byte.
+///
+/// A tap is added to a subscription with [`SubscriberBuilder::capture`]. It is cheap to clone,
+/// all clones record to the same writer, so a clone can be kept to check on the recording. When
+/// writing fails, recording stops and the error is kept until [`CaptureTap::take_error`] is
+/// called, the subscription itself continues.
+///
+/// Messages received on a socket connected to several endpoints are recorded with an empty
+/// endpoint. Use one of the `_with_source` subscriptions to record the endpoint of every message.
+///
+/// ```no_run
+/// use ghostcore_zmq::{CaptureTap, CaptureWriter, SubscriberBuilder};
+///
+/// let tap = CaptureTap::new(CaptureWriter::open_file("capture.gzmq").unwrap());
+///
+/// let rx = SubscriberBuilder::new(&["tcp://127.0.0.1:28332"])
+///     .capture(tap.clone())
+///     .subscribe_receiver()
+///     .unwrap();
+///
+/// for msg in rx {
+///     if let Err(err) = msg {
+///         println!("Error receiving message: {err}, stopping");
+///         break;
+///     }
+/// }
+///
+/// println!("recorded {} messages", tap.recorded());
+/// if let Some(err) = tap.take_error() {
+///     println!("recording failed: {err}");
+/// }
+/// ```
+///
+/// [`Error::BitcoinDeserialization`]: crate::Error::BitcoinDeserialization
+/// [`SubscriberBuilder::capture`]: crate::SubscriberBuilder::capture
+#[derive(Clone)]
+pub struct CaptureTap {
+    shared: Arc<Shared>,
+}
+
+impl CaptureTap {
+    /// Creates a [`CaptureTap`] that records to `writer`.
+    pub fn new<W>(writer: CaptureWriter<W>) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        let writer: Box<dyn Write + Send> = Box::new(writer.into_inner());
+
+        Self {
+            shared: Arc::new(Shared {
+                writer: Mutex::new(Some(CaptureWriter::append(writer))),
+                ..Shared::default()
+            }),
+        }
+    }
+
+    /// Returns the number of messages recorded so far.
+    #[inline]
+    pub fn recorded(&self) -> u64 {
+        self.shared.recorded.load(Ordering::Relaxed)
+    }
+
+    /// Returns true if recording stopped because writing failed.
+    #[inline]
+    pub fn has_failed(&self) -> bool {
+        self.writer().is_none()
+    }
+
+    /// Returns the error that stopped recording, if any. The error is only returned once.
+    #[inline]
+    pub fn take_error(&self) -> Option<io::Error> {
+        self.shared
+            .error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+    }
+
+    /// Records a multipart received from `endpoint`, unless recording stopped.
+    pub(crate) fn record(&self, endpoint: &str, parts: &[zmq::Message]) {
+        let mut writer = self.writer();
+        let Some(capture) = writer.as_mut() else {
+            return;
+        };
+
+        let record = CaptureRecord {
+            timestamp: SystemTime::now(),
+            endpoint: endpoint.to_owned(),
+            parts: parts.iter().map(|part| part.to_vec()).collect(),
+        };
+
+        match capture.write(&record) {
+            Ok(()) => {
+                self.shared.recorded.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                drop(writer);
+                self.fail(err);
+            }
+        }
+    }
+
+    /// Stops recording because of `err`.
+    pub(crate) fn fail(&self, err: io::Error) {
+        *self.writer() = None;
+        *self
+            .shared
+            .error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner()) = Some(err);
+    }
+
+    fn writer(&self) -> MutexGuard<'_, Option<CaptureWriter<Box<dyn Write + Send>>>> {
+        self.shared
+            .writer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl fmt::Debug for CaptureTap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CaptureTap")
+            .field("recorded", &self.recorded())
+            .field("failed", &self.has_failed())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        capture::tests::ChannelWriter, testing::MockPublisher, CaptureReader, CaptureTap,
+        CaptureWriter, Error, Message,
+    };
+    use bitcoin::{hashes::Hash, BlockHash};
+    use core::time::Duration;
+    use std::{
+        io::{self, Cursor, Write},
+        sync::mpsc::channel,
+    };
+
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tap() {
+        let mut publisher = MockPublisher::inproc().unwrap();
+
+        let (tx, rx) = channel();
+        let tap = CaptureTap::new(CaptureWriter::new(ChannelWriter(tx)).unwrap());
+        let messages = publisher
+            .subscriber()
+            .capture(tap.clone())
+            .subscribe_receiver()
+            .unwrap();
+
+        assert!(publisher
+            .wait_for_subscription(Duration::from_secs(10))
+            .unwrap());
+
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        publisher.send(&msg).unwrap();
+        publisher
+            .send_raw(&[b"hashblock".as_slice(), b"\x00"])
+            .unwrap();
+
+        assert_eq!(messages.recv().unwrap().unwrap(), msg);
+        assert!(matches!(
+            messages.recv().unwrap(),
+            Err(Error::InvalidMutlipartLength(2))
+        ));
+        assert_eq!(tap.recorded(), 2);
+
+        let bytes: Vec<u8> = rx.try_iter().flatten().collect();
+        let records: Vec<_> = CaptureReader::new(Cursor::new(bytes))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].endpoint, publisher.endpoint());
+        assert_eq!(records[0].to_message().unwrap(), msg);
+        assert_eq!(records[1].parts, [b"hashblock".to_vec(), vec![0]]);
+    }
+
+    #[test]
+    fn write_error() {
+        let mut publisher = MockPublisher::inproc().unwrap();
+
+        let tap = CaptureTap::new(CaptureWriter::append(FailingWriter));
+        let messages = publisher
+            .subscriber()
+            .capture(tap.clone())
+            .subscribe_receiver()
+            .unwrap();
+
+        assert!(publisher
+            .wait_for_subscription(Duration::from_secs(10))
+            .unwrap());
+
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        publisher.send(&msg).unwrap();
+
+        // the subscription continues without recording
+        assert_eq!(messages.recv().unwrap().unwrap(), msg);
+        assert!(tap.has_failed());
+        assert_eq!(tap.recorded(), 0);
+        assert_eq!(tap.take_error().unwrap().kind(), io::ErrorKind::BrokenPipe);
+        assert!(tap.take_error().is_none());
+    }
+}
@@ -0,0 +1,460 @@
+use crate::{block::GhostBlockHeader, message::Message, sequence_message::SequenceMessage};
+use bitcoin::BlockHash;
+use core::{fmt, mem};
+use std::collections::VecDeque;
+
+/// A change of the chain tip, produced by a [`ChainTracker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    /// A block was connected on top of the previous tip.
+    Connected(BlockHash),
+    /// One or more blocks were disconnected and a new branch was connected.
+    ///
+    /// `disconnected` is in the order the blocks were disconnected (old tip first), `connected` in
+    /// the order they were connected (new tip last). `fork_point` is the last block both chains
+    /// have in common, or [`None`] if it is not in the tracked history.
+    ///
+    /// A [`BlockConnect`] does not say whether more blocks of the new branch follow, so the reorg
+    /// is only reported when the branch is complete: when the node announces the new tip with a
+    /// `hashblock` or `rawblock` message, when the next block is disconnected, or when
+    /// [`ChainTracker::flush`] is called.
+    ///
+    /// [`BlockConnect`]: SequenceMessage::BlockConnect
+    Reorg {
+        disconnected: Vec<BlockHash>,
+        connected: Vec<BlockHash>,
+        fork_point: Option<BlockHash>,
+    },
+    /// A block header was received whose parent is not in the tracked history, because messages
+    /// were missed or the history is too short. The history restarts at this block. Only produced
+    /// by [`ChainTracker::on_header`].
+    Unlinked(BlockHash),
+}
+
+impl fmt::Display for ChainEvent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connected(blockhash) => write!(f, "Connected({blockhash})"),
+            Self::Reorg {
+                disconnected,
+                connected,
+                fork_point,
+            } => {
+                write!(
+                    f,
+                    "Reorg({} disconnected, {} connected, fork_point=",
+                    disconnected.len(),
+                    connected.len()
+                )?;
+                match fork_point {
+                    Some(fork_point) => write!(f, "{fork_point})"),
+                    None => f.write_str("unknown)"),
+                }
+            }
+            Self::Unlinked(blockhash) => write!(f, "Unlinked({blockhash})"),
+        }
+    }
+}
+
+/// Keeps track of the last blocks of the active chain and detects reorgs.
+///
+/// Blocks can be fed from the `sequence` topic ([`BlockConnect`] and [`BlockDisconnect`]) or from
+/// the `rawblock` topic, where the previous block hash of every block is used to find the fork
+/// point. Blocks that are already in the history (for example from a `rawblock` message and a
+/// `sequence` message for the same block) are ignored.
+///
+/// ```
+/// use ghostcore_zmq::{ChainEvent, ChainTracker, Message, SequenceMessage};
+/// use bitcoin::{hashes::Hash, BlockHash};
+///
+/// let hash = |n| BlockHash::from_byte_array([n; 32]);
+/// let mut tracker = ChainTracker::new(100);
+///
+/// for n in 1..=3 {
+///     let blockhash = hash(n);
+///     tracker.on_sequence(&SequenceMessage::BlockConnect { blockhash });
+/// }
+///
+/// tracker.on_sequence(&SequenceMessage::BlockDisconnect { blockhash: hash(3) });
+/// for n in 4..=5 {
+///     let blockhash = hash(n);
+///     assert_eq!(tracker.on_sequence(&SequenceMessage::BlockConnect { blockhash }), None);
+/// }
+/// assert_eq!(tracker.tip(), Some(hash(5)));
+///
+/// // the node announces the new tip once the new branch is connected
+/// let event = tracker.on_message(&Message::HashBlock(hash(5), 0));
+///
+/// assert_eq!(
+///     event,
+///     Some(ChainEvent::Reorg {
+///         disconnected: vec![hash(3)],
+///         connected: vec![hash(4), hash(5)],
+///         fork_point: Some(hash(2)),
+///     })
+/// );
+/// ```
+///
+/// [`BlockConnect`]: SequenceMessage::BlockConnect
+/// [`BlockDisconnect`]: SequenceMessage::BlockDisconnect
+#[derive(Debug, Clone)]
+pub struct ChainTracker {
+    capacity: usize,
+    chain: VecDeque<BlockHash>,
+    disconnected: Vec<BlockHash>,
+    connected: Vec<BlockHash>,
+    fork_point: Option<BlockHash>,
+}
+
+impl ChainTracker {
+    /// Creates a new [`ChainTracker`] that remembers the last `capacity` blocks. Reorgs deeper
+    /// than that are reported without a fork point.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    #[inline]
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "capacity must be at least 1");
+
+        Self {
+            capacity,
+            chain: VecDeque::with_capacity(capacity),
+            disconnected: Vec::new(),
+            connected: Vec::new(),
+            fork_point: None,
+        }
+    }
+
+    /// Returns the current tip, or [`None`] if no blocks are known.
+    #[inline]
+    pub fn tip(&self) -> Option<BlockHash> {
+        self.chain.back().copied()
+    }
+
+    /// Returns true if `blockhash` is in the tracked history of the active chain.
+    #[inline]
+    pub fn contains(&self, blockhash: &BlockHash) -> bool {
+        self.chain.contains(blockhash)
+    }
+
+    /// Returns an iterator over the tracked history of the active chain, oldest block first.
+    #[inline]
+    pub fn blocks(&self) -> impl DoubleEndedIterator<Item = &BlockHash> + ExactSizeIterator {
+        self.chain.iter()
+    }
+
+    /// Returns the blocks disconnected by the reorg that has not been reported yet, in the order
+    /// they were disconnected.
+    #[inline]
+    pub fn pending_disconnected(&self) -> &[BlockHash] {
+        &self.disconnected
+    }
+
+    /// Returns the blocks connected by the reorg that has not been reported yet, in the order
+    /// they were connected. These are already part of the active chain.
+    #[inline]
+    pub fn pending_connected(&self) -> &[BlockHash] {
+        &self.connected
+    }
+
+    /// Reports the pending reorg, if blocks were connected since the last blocks were
+    /// disconnected. When only the `sequence` topic is used, the end of the new branch is only
+    /// known when the next block is disconnected, so call this when no more blocks are expected
+    /// right away (for example when receiving times out).
+    pub fn flush(&mut self) -> Option<ChainEvent> {
+        if self.connected.is_empty() {
+            return None;
+        }
+
+        Some(ChainEvent::Reorg {
+            disconnected: mem::take(&mut self.disconnected),
+            connected: mem::take(&mut self.connected),
+            fork_point: self.fork_point.take(),
+        })
+    }
+
+    /// Processes a [`SequenceMessage`]. Mempool messages are ignored. Disconnected blocks are only
+    /// reported with the new branch, see [`ChainEvent::Reorg`], so a [`BlockDisconnect`] can
+    /// complete the previous reorg.
+    ///
+    /// [`BlockDisconnect`]: SequenceMessage::BlockDisconnect
+    pub fn on_sequence(&mut self, message: &SequenceMessage) -> Option<ChainEvent> {
+        match *message {
+            SequenceMessage::BlockConnect { blockhash } => self.connect(blockhash),
+            SequenceMessage::BlockDisconnect { blockhash } => {
+                let reorg = self.flush();
+                self.disconnect(blockhash);
+                reorg
+            }
+            SequenceMessage::MempoolAcceptance { .. } | SequenceMessage::MempoolRemoval { .. } => {
+                None
+            }
+        }
+    }
+
+    /// Processes a block header (for example of a block from the `rawblock` topic), using its
+    /// previous block hash to detect reorgs. The node only sends `rawblock` messages for a new
+    /// tip, so the header completes a pending reorg.
+    ///
+    /// A header only links to its parent, so missed blocks can not be filled in: when the tip
+    /// jumps by more than one block (because `rawblock` messages were missed, for example when
+    /// the receive high water mark was reached while the node caught up), the header is reported
+    /// as [`ChainEvent::Unlinked`] and the history restarts at it. Use `sequence` messages to
+    /// track every block.
+    pub fn on_header(&mut self, header: &GhostBlockHeader) -> Option<ChainEvent> {
+        let blockhash = header.block_hash();
+        let prev = header.prev_blockhash;
+
+        if self.contains(&blockhash) {
+            return self.on_tip(blockhash);
+        }
+
+        if self.chain.is_empty() || self.tip() == Some(prev) {
+            return self.connect(blockhash).or_else(|| self.flush());
+        }
+
+        let Some(fork) = self.chain.iter().rposition(|hash| *hash == prev) else {
+            self.chain.clear();
+            self.disconnected.clear();
+            self.connected.clear();
+            self.fork_point = None;
+            self.push(blockhash);
+            return Some(ChainEvent::Unlinked(blockhash));
+        };
+
+        for hash in self.chain.drain(fork + 1..).rev() {
+            // blocks of the pending branch were never reported as connected
+            if self.connected.last() == Some(&hash) {
+                self.connected.pop();
+            } else {
+                self.disconnected.push(hash);
+            }
+        }
+
+        self.connect(blockhash).or_else(|| self.flush())
+    }
+
+    /// Processes a [`Message`]. `sequence` and `rawblock` messages are handled by
+    /// [`ChainTracker::on_sequence`] and [`ChainTracker::on_header`], other messages are ignored.
+    ///
+    /// A `hashblock` message does not say which block it builds on (the node also sends one for
+    /// the new tip after a reorg), so it can not move the tip. It only completes a pending reorg
+    /// if it announces the current tip.
+    pub fn on_message(&mut self, message: &Message) -> Option<ChainEvent> {
+        match message {
+            Message::Sequence(sm, _) => self.on_sequence(sm),
+            Message::Block(block, _) => self.on_header(&block.header),
+            Message::HashBlock(blockhash, _) => self.on_tip(*blockhash),
+            Message::HashTx(..) | Message::HashWTx(..) | Message::Tx(..) => None,
+        }
+    }
+
+    /// Completes a pending reorg if `blockhash`, a block that was announced as the new tip, is
+    /// the current tip.
+    fn on_tip(&mut self, blockhash: BlockHash) -> Option<ChainEvent> {
+        if self.tip() == Some(blockhash) {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    fn connect(&mut self, blockhash: BlockHash) -> Option<ChainEvent> {
+        // already connected, for example the tip from both a `rawblock` and a `sequence` message
+        if self.contains(&blockhash) {
+            return None;
+        }
+
+        if self.disconnected.is_empty() {
+            self.push(blockhash);
+
+            return Some(ChainEvent::Connected(blockhash));
+        }
+
+        if self.connected.is_empty() {
+            self.fork_point = self.tip();
+        }
+        self.push(blockhash);
+        self.connected.push(blockhash);
+
+        None
+    }
+
+    fn disconnect(&mut self, blockhash: BlockHash) {
+        if self.tip() == Some(blockhash) {
+            self.chain.pop_back();
+        } else if self.contains(&blockhash) {
+            // blocks on top of it were disconnected without us being notified
+            let index = self
+                .chain
+                .iter()
+                .rposition(|hash| *hash == blockhash)
+                .unwrap();
+            self.disconnected.extend(self.chain.drain(index..).rev());
+            return;
+        } else if !self.chain.is_empty() {
+            // unknown block, the history can not be trusted anymore
+            self.chain.clear();
+        }
+
+        self.disconnected.push(blockhash);
+    }
+
+    fn push(&mut self, blockhash: BlockHash) {
+        if self.chain.len() == self.capacity {
+            self.chain.pop_front();
+        }
+        self.chain.push_back(blockhash);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        block::tests::sample_block, ChainEvent, ChainTracker, GhostBlock, GhostBlockHeader,
+        Message, SequenceMessage,
+    };
+    use bitcoin::{hashes::Hash, BlockHash};
+
+    fn hash(n: u8) -> BlockHash {
+        BlockHash::from_byte_array([n; 32])
+    }
+
+    fn connect(n: u8) -> SequenceMessage {
+        SequenceMessage::BlockConnect { blockhash: hash(n) }
+    }
+
+    fn disconnect(n: u8) -> SequenceMessage {
+        SequenceMessage::BlockDisconnect { blockhash: hash(n) }
+    }
+
+    #[test]
+    fn sequence() {
+        let mut tracker = ChainTracker::new(3);
+
+        for n in 1..=4 {
+            assert_eq!(
+                tracker.on_sequence(&connect(n)),
+                Some(ChainEvent::Connected(hash(n)))
+            );
+        }
+        assert_eq!(tracker.on_sequence(&connect(4)), None);
+        // a deeper block that is already known is not connected again
+        assert_eq!(tracker.on_sequence(&connect(3)), None);
+        assert!(tracker.blocks().copied().eq([hash(2), hash(3), hash(4)]));
+
+        assert_eq!(tracker.on_sequence(&disconnect(4)), None);
+        assert_eq!(tracker.on_sequence(&disconnect(3)), None);
+        assert_eq!(tracker.pending_disconnected(), [hash(4), hash(3)]);
+        assert_eq!(tracker.on_sequence(&connect(5)), None);
+        // `hashblock` messages do not move the tip
+        assert_eq!(tracker.on_message(&Message::HashBlock(hash(6), 0)), None);
+        assert_eq!(tracker.tip(), Some(hash(5)));
+        assert_eq!(tracker.on_sequence(&connect(6)), None);
+        assert_eq!(tracker.pending_connected(), [hash(5), hash(6)]);
+
+        // the new tip is announced, the new branch is complete
+        assert_eq!(
+            tracker.on_message(&Message::HashBlock(hash(6), 0)),
+            Some(ChainEvent::Reorg {
+                disconnected: vec![hash(4), hash(3)],
+                connected: vec![hash(5), hash(6)],
+                fork_point: Some(hash(2)),
+            })
+        );
+        assert_eq!(tracker.on_message(&Message::HashBlock(hash(6), 0)), None);
+
+        // deeper than the history
+        for n in [6, 5, 2, 1] {
+            assert_eq!(tracker.on_sequence(&disconnect(n)), None);
+        }
+        assert_eq!(tracker.on_sequence(&connect(7)), None);
+        // the next disconnect completes the new branch
+        assert_eq!(
+            tracker.on_sequence(&disconnect(7)),
+            Some(ChainEvent::Reorg {
+                disconnected: vec![hash(6), hash(5), hash(2), hash(1)],
+                connected: vec![hash(7)],
+                fork_point: None,
+            })
+        );
+
+        assert_eq!(tracker.on_sequence(&connect(8)), None);
+        assert_eq!(
+            tracker.flush(),
+            Some(ChainEvent::Reorg {
+                disconnected: vec![hash(7)],
+                connected: vec![hash(8)],
+                fork_point: None,
+            })
+        );
+        assert_eq!(tracker.flush(), None);
+        assert_eq!(
+            tracker.on_sequence(&connect(9)),
+            Some(ChainEvent::Connected(hash(9)))
+        );
+    }
+
+    #[test]
+    fn headers() {
+        let base = sample_block().header;
+        let child = |parent: &GhostBlockHeader, nonce| GhostBlockHeader {
+            prev_blockhash: parent.block_hash(),
+            nonce,
+            ..*parent
+        };
+
+        let a = child(&base, 1);
+        let b = child(&a, 2);
+        let c = child(&b, 3);
+        let b2 = child(&a, 4);
+
+        let mut tracker = ChainTracker::new(10);
+        for header in [&base, &a, &b, &c] {
+            assert_eq!(
+                tracker.on_header(header),
+                Some(ChainEvent::Connected(header.block_hash()))
+            );
+        }
+        assert_eq!(tracker.on_header(&b), None);
+
+        assert_eq!(
+            tracker.on_header(&b2),
+            Some(ChainEvent::Reorg {
+                disconnected: vec![c.block_hash(), b.block_hash()],
+                connected: vec![b2.block_hash()],
+                fork_point: Some(a.block_hash()),
+            })
+        );
+
+        // the node announces the new tip with `hashblock` before `rawblock`
+        let c2 = child(&b2, 6);
+        let a2 = child(&base, 7);
+        assert_eq!(
+            tracker.on_message(&Message::HashBlock(a2.block_hash(), 0)),
+            None
+        );
+        let rawblock = GhostBlock {
+            header: a2,
+            ..sample_block()
+        };
+        assert_eq!(
+            tracker.on_message(&Message::Block(rawblock, 0)),
+            Some(ChainEvent::Reorg {
+                disconnected: vec![b2.block_hash(), a.block_hash()],
+                connected: vec![a2.block_hash()],
+                fork_point: Some(base.block_hash()),
+            })
+        );
+        assert_eq!(tracker.tip(), Some(a2.block_hash()));
+
+        let unlinked = child(&c2, 5);
+        assert_eq!(
+            tracker.on_header(&unlinked),
+            Some(ChainEvent::Unlinked(unlinked.block_hash()))
+        );
+        assert!(tracker.blocks().copied().eq([unlinked.block_hash()]));
+    }
+}
@@ -0,0 +1,507 @@
+use crate::{
+    adapter::{Adapter, Process},
+    error::Result,
+    message::Message,
+    received::Received,
+    topic::Topic,
+};
+use bitcoin::hashes::{sha256d, Hash};
+use core::{fmt, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Instant,
+};
+
+#[cfg(feature = "async")]
+use core::{
+    pin::Pin,
+    task::{Context as AsyncContext, Poll},
+};
+#[cfg(feature = "async")]
+use futures_util::stream::Stream;
+
+/// Identifies a logical event, the same event published by different nodes has the same
+/// [`DedupKey`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DedupKey {
+    pub topic: Topic,
+    /// The block hash or txid (in internal byte order, displayed reversed). For `hashwtx` messages
+    /// this is the hash of the txid and the wallet name.
+    pub hash: [u8; 32],
+    /// The label of a `sequence` message, 0 for other topics.
+    pub label: u8,
+}
+
+impl DedupKey {
+    /// Returns the [`DedupKey`] of a [`Message`]. Sequence numbers are not part of the key, these
+    /// differ between nodes.
+    pub fn of(message: &Message) -> Self {
+        let (hash, label) = match message {
+            Message::HashBlock(blockhash, _) => (blockhash.to_byte_array(), 0),
+            Message::HashTx(txid, _) => (txid.to_byte_array(), 0),
+            Message::HashWTx(hash_wtx, _) => (
+                sha256d::Hash::hash(&hash_wtx.serialize_to_vec()).to_byte_array(),
+                0,
+            ),
+            Message::Block(block, _) => (block.block_hash().to_byte_array(), 0),
+            Message::Tx(tx, _) => (tx.txid().to_byte_array(), 0),
+            Message::Sequence(sm, _) => {
+                let mut hash = sm.inner_hash_as_bytes();
+                hash.reverse();
+                (hash, sm.label())
+            }
+        };
+
+        Self {
+            topic: message.topic_type(),
+            hash,
+            label,
+        }
+    }
+}
+
+impl fmt::Display for DedupKey {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ", self.topic)?;
+        for byte in self.hash.iter().rev() {
+            write!(f, "{byte:02x}")?;
+        }
+        if self.label != 0 {
+            write!(f, " {}", self.label as char)?;
+        }
+        Ok(())
+    }
+}
+
+/// A delivery of an event by an endpoint, `delay` is the time since the first delivery.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub endpoint: String,
+    pub delay: Duration,
+}
+
+/// An event produced by a [`Deduplicator`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DedupEvent {
+    /// The first delivery of an event.
+    New(Received),
+    /// A later delivery of an event by another (or the same) endpoint, `delay` after the first
+    /// delivery.
+    Duplicate {
+        key: DedupKey,
+        endpoint: String,
+        delay: Duration,
+    },
+    /// An endpoint did not deliver the last `missed` events that other endpoints delivered.
+    Stalled { endpoint: String, missed: u32 },
+    /// A stalled endpoint delivered an event again.
+    Recovered { endpoint: String },
+}
+
+impl fmt::Display for DedupEvent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::New(received) => write!(f, "New({received})"),
+            Self::Duplicate {
+                key,
+                endpoint,
+                delay,
+            } => write!(f, "Duplicate({key} from {endpoint}, delay={delay:?})"),
+            Self::Stalled { endpoint, missed } => {
+                write!(f, "Stalled({endpoint}, missed={missed})")
+            }
+            Self::Recovered { endpoint } => write!(f, "Recovered({endpoint})"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct EndpointState {
+    endpoint: String,
+    missed: u32,
+    stalled: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    first_seen: Instant,
+    deliveries: Vec<Delivery>,
+}
+
+/// Merges the messages of redundant publishers, yielding every logical event (identified by its
+/// [`DedupKey`]) once.
+///
+/// Events are remembered for a limited time and up to a maximum number of events. When an event
+/// is forgotten, every endpoint that did not deliver it is counted as having missed it, an
+/// endpoint that misses a number of events in a row is reported as [`DedupEvent::Stalled`].
+/// Deliveries of an event after it was forgotten are reported as [`DedupEvent::New`] again.
+///
+/// ```no_run
+/// use ghostcore_zmq::{subscribe_receiver_with_source, DedupEvent, Deduplicated, Deduplicator};
+///
+/// let endpoints = ["tcp://10.0.0.1:28332", "tcp://10.0.0.2:28332"];
+/// let rx = subscribe_receiver_with_source(&endpoints).unwrap();
+///
+/// for event in Deduplicated::new(rx.into_iter(), Deduplicator::new(&endpoints)) {
+///     match event {
+///         Ok(DedupEvent::New(received)) => println!("Received message: {received}"),
+///         Ok(event) => println!("{event}"),
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deduplicator {
+    endpoints: Vec<EndpointState>,
+    window: Duration,
+    capacity: usize,
+    stall_threshold: u32,
+    entries: HashMap<DedupKey, Entry>,
+    order: VecDeque<DedupKey>,
+}
+
+impl Deduplicator {
+    /// Creates a new [`Deduplicator`] for the given endpoints, remembering events for 60 seconds
+    /// and up to 100000 events, reporting an endpoint as stalled after 10 missed events.
+    pub fn new(endpoints: &[&str]) -> Self {
+        Self {
+            endpoints: endpoints
+                .iter()
+                .map(|endpoint| EndpointState {
+                    endpoint: (*endpoint).to_owned(),
+                    missed: 0,
+                    stalled: false,
+                })
+                .collect(),
+            window: Duration::from_secs(60),
+            capacity: 100_000,
+            stall_threshold: 10,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Sets how long an event is remembered after its first delivery.
+    #[inline]
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Sets the maximum number of events remembered.
+    #[inline]
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Sets the number of events an endpoint has to miss in a row to be reported as stalled.
+    #[inline]
+    pub fn stall_threshold(mut self, stall_threshold: u32) -> Self {
+        self.stall_threshold = stall_threshold;
+        self
+    }
+
+    /// Returns the deliveries of an event if it is still remembered, in the order they were
+    /// received.
+    #[inline]
+    pub fn deliveries(&self, key: &DedupKey) -> Option<&[Delivery]> {
+        Some(&self.entries.get(key)?.deliveries)
+    }
+
+    /// Returns true if `endpoint` is currently reported as stalled.
+    #[inline]
+    pub fn is_stalled(&self, endpoint: &str) -> bool {
+        self.endpoints
+            .iter()
+            .any(|state| state.endpoint == endpoint && state.stalled)
+    }
+
+    /// Returns the number of events remembered.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if no events are remembered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Processes a received message, see [`Deduplicator::push_at`].
+    #[inline]
+    pub fn push(&mut self, received: Received) -> Vec<DedupEvent> {
+        self.push_at(received, Instant::now())
+    }
+
+    /// Processes a message received at `now`, returning the resulting events. Events that left
+    /// the window are forgotten first.
+    pub fn push_at(&mut self, received: Received, now: Instant) -> Vec<DedupEvent> {
+        let mut events = self.expire_at(now);
+
+        let key = DedupKey::of(&received.message);
+
+        let index = match self
+            .endpoints
+            .iter()
+            .position(|state| state.endpoint == received.endpoint)
+        {
+            Some(index) => index,
+            None => {
+                self.endpoints.push(EndpointState {
+                    endpoint: received.endpoint.clone(),
+                    missed: 0,
+                    stalled: false,
+                });
+                self.endpoints.len() - 1
+            }
+        };
+        let state = &mut self.endpoints[index];
+        state.missed = 0;
+        if state.stalled {
+            state.stalled = false;
+            events.push(DedupEvent::Recovered {
+                endpoint: state.endpoint.clone(),
+            });
+        }
+
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                let delay = now.saturating_duration_since(entry.first_seen);
+                entry.deliveries.push(Delivery {
+                    endpoint: received.endpoint.clone(),
+                    delay,
+                });
+                events.push(DedupEvent::Duplicate {
+                    key,
+                    endpoint: received.endpoint,
+                    delay,
+                });
+            }
+            None => {
+                if self.order.len() >= self.capacity {
+                    self.forget_oldest(&mut events);
+                }
+
+                self.entries.insert(
+                    key,
+                    Entry {
+                        first_seen: now,
+                        deliveries: vec![Delivery {
+                            endpoint: received.endpoint.clone(),
+                            delay: Duration::ZERO,
+                        }],
+                    },
+                );
+                self.order.push_back(key);
+                events.push(DedupEvent::New(received));
+            }
+        }
+
+        events
+    }
+
+    /// Forgets all events that left the window at `now`, returning the resulting
+    /// [`DedupEvent::Stalled`] events.
+    pub fn expire_at(&mut self, now: Instant) -> Vec<DedupEvent> {
+        let mut events = Vec::new();
+
+        while let Some(key) = self.order.front() {
+            let first_seen = self.entries[key].first_seen;
+            if now.saturating_duration_since(first_seen) < self.window {
+                break;
+            }
+            self.forget_oldest(&mut events);
+        }
+
+        events
+    }
+
+    fn forget_oldest(&mut self, events: &mut Vec<DedupEvent>) {
+        let Some(key) = self.order.pop_front() else {
+            return;
+        };
+        let entry = self.entries.remove(&key).unwrap();
+
+        for state in &mut self.endpoints {
+            if entry
+                .deliveries
+                .iter()
+                .any(|delivery| delivery.endpoint == state.endpoint)
+            {
+                continue;
+            }
+
+            state.missed += 1;
+
+            if state.missed >= self.stall_threshold && !state.stalled {
+                state.stalled = true;
+                events.push(DedupEvent::Stalled {
+                    endpoint: state.endpoint.clone(),
+                    missed: state.missed,
+                });
+            }
+        }
+    }
+}
+
+/// Wraps an [`Iterator`] (like a [`Receiver`]) or a `Stream` of [`Received`] messages and merges
+/// them using a [`Deduplicator`].
+///
+/// [`Receiver`]: std::sync::mpsc::Receiver
+#[derive(Debug)]
+pub struct Deduplicated<I> {
+    adapter: Adapter<I, Deduplicator>,
+}
+
+impl<I> Deduplicated<I> {
+    /// Wraps `inner` using `deduplicator`.
+    #[inline]
+    pub fn new(inner: I, deduplicator: Deduplicator) -> Self {
+        Self {
+            adapter: Adapter::new(inner, deduplicator),
+        }
+    }
+
+    /// Returns a reference to the [`Deduplicator`] used.
+    #[inline]
+    pub fn deduplicator(&self) -> &Deduplicator {
+        &self.adapter.processor
+    }
+
+    /// Returns the wrapped iterator or stream.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.adapter.inner
+    }
+}
+
+impl Process for Deduplicator {
+    type Input = Received;
+    type Output = DedupEvent;
+
+    #[inline]
+    fn process(&mut self, received: Received, out: &mut VecDeque<DedupEvent>) {
+        out.extend(self.push(received));
+    }
+}
+
+impl<I: Iterator<Item = Result<Received>>> Iterator for Deduplicated<I> {
+    type Item = Result<DedupEvent>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.adapter.next()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: Stream<Item = Result<Received>> + Unpin> Stream for Deduplicated<S> {
+    type Item = Result<DedupEvent>;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut AsyncContext<'_>) -> Poll<Option<Self::Item>> {
+        self.adapter.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        adapter::tests::{hashblock, received},
+        DedupEvent, DedupKey, Deduplicator, Delivery, Topic,
+    };
+    use core::time::Duration;
+    use std::time::Instant;
+
+    #[test]
+    fn dedup() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        let mut dedup = Deduplicator::new(&["a", "b", "c"])
+            .window(Duration::from_secs(1))
+            .stall_threshold(2);
+
+        assert_eq!(
+            dedup.push_at(received("a", hashblock(1)), at(0)),
+            [DedupEvent::New(received("a", hashblock(1)))]
+        );
+
+        let key = DedupKey::of(&received("b", hashblock(1)).message);
+        assert_eq!(key.topic, Topic::HashBlock);
+        assert_eq!(
+            dedup.push_at(received("b", hashblock(1)), at(20)),
+            [DedupEvent::Duplicate {
+                key,
+                endpoint: "b".to_owned(),
+                delay: Duration::from_millis(20)
+            }]
+        );
+        assert_eq!(
+            dedup.deliveries(&key).unwrap(),
+            [
+                Delivery {
+                    endpoint: "a".to_owned(),
+                    delay: Duration::ZERO
+                },
+                Delivery {
+                    endpoint: "b".to_owned(),
+                    delay: Duration::from_millis(20)
+                }
+            ]
+        );
+
+        // "c" never delivers
+        dedup.push_at(received("a", hashblock(2)), at(500));
+        assert_eq!(
+            dedup.push_at(received("a", hashblock(3)), at(1000)).len(),
+            1
+        );
+        assert_eq!(
+            dedup.push_at(received("b", hashblock(4)), at(1600)),
+            [
+                DedupEvent::Stalled {
+                    endpoint: "c".to_owned(),
+                    missed: 2
+                },
+                DedupEvent::New(received("b", hashblock(4)))
+            ]
+        );
+        assert!(dedup.is_stalled("c"));
+        assert_eq!(dedup.len(), 2);
+
+        assert_eq!(
+            dedup.push_at(received("c", hashblock(4)), at(1700)),
+            [
+                DedupEvent::Recovered {
+                    endpoint: "c".to_owned()
+                },
+                DedupEvent::Duplicate {
+                    key: DedupKey::of(&received("c", hashblock(4)).message),
+                    endpoint: "c".to_owned(),
+                    delay: Duration::from_millis(100)
+                }
+            ]
+        );
+        assert!(!dedup.is_stalled("c"));
+    }
+
+    #[test]
+    fn capacity() {
+        let now = Instant::now();
+        let mut dedup = Deduplicator::new(&["a", "b"]).capacity(2);
+
+        for n in 0..3 {
+            dedup.push_at(received("a", hashblock(n)), now);
+        }
+        assert_eq!(dedup.len(), 2);
+        assert!(matches!(
+            dedup.push_at(received("b", hashblock(0)), now)[..],
+            [DedupEvent::New(_)]
+        ));
+    }
+}
@@ -4,6 +4,7 @@ use crate::{
 };
 use bitcoin::consensus;
 use core::{cmp::min, fmt};
+use std::io;
 
 pub type Result<T> = core::result::Result<T, Error>;
 
@@ -16,9 +17,11 @@ pub enum Error {
     InvalidSequenceMessageLength(usize),
     InvalidSequenceMessageLabel(u8),
     Invalid256BitHashLength(usize),
+    InvalidHashWTxLength(usize),
     BitcoinDeserialization(consensus::encode::Error),
     Zmq(zmq::Error),
     MonitorMessage(MonitorMessageError),
+    Io(io::Error),
 }
 
 impl Error {
@@ -80,6 +83,13 @@ impl From<MonitorMessageError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    #[inline]
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
 impl fmt::Display for Error {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -121,12 +131,19 @@ impl fmt::Display for Error {
             Self::Invalid256BitHashLength(len) => {
                 write!(f, "invalid hash length: {len} (expected 32)")
             }
+            Self::InvalidHashWTxLength(len) => {
+                write!(
+                    f,
+                    "invalid message length {len} of message type 'hashwtx' (expected at least 32)"
+                )
+            }
 
             Self::BitcoinDeserialization(e) => {
                 write!(f, "bitcoin consensus deserialization error: {e}")
             }
             Self::Zmq(e) => write!(f, "ZMQ Error: {e}"),
             Self::MonitorMessage(err) => write!(f, "unable to parse monitor message: {err}"),
+            Self::Io(err) => write!(f, "I/O error: {err}"),
         }
     }
 }
@@ -138,13 +155,15 @@ impl std::error::Error for Error {
             Self::BitcoinDeserialization(e) => e,
             Self::Zmq(e) => e,
             Self::MonitorMessage(e) => e,
+            Self::Io(e) => e,
             Self::InvalidMutlipartLength(_)
             | Self::InvalidTopic(_, _)
             | Self::InvalidDataLength(_)
             | Self::InvalidSequenceLength(_)
             | Self::InvalidSequenceMessageLength(_)
             | Self::InvalidSequenceMessageLabel(_)
-            | Self::Invalid256BitHashLength(_) => return None,
+            | Self::Invalid256BitHashLength(_)
+            | Self::InvalidHashWTxLength(_) => return None,
         })
     }
 }
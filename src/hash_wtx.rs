@@ -0,0 +1,122 @@
+use crate::error::{Error, Result};
+use bitcoin::{hashes::Hash, Txid};
+use core::fmt;
+
+/// The data of a `hashwtx` message: the txid of a transaction that affects a wallet, followed by
+/// the name of that wallet.
+///
+/// On the wire, the txid is sent as 32 bytes (in reversed byte order, like `hashtx`), followed by
+/// the UTF-8 encoded wallet name without padding or a length prefix. The wallet name may be empty
+/// (the default wallet) and has no maximum length.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct HashWTx {
+    pub txid: Txid,
+    pub wallet: String,
+}
+
+impl HashWTx {
+    /// Returns the length of this [`HashWTx`] when serialized.
+    #[inline]
+    pub fn raw_length(&self) -> usize {
+        32 + self.wallet.len()
+    }
+
+    /// Deserializes bytes to a [`HashWTx`]. Invalid UTF-8 in the wallet name is replaced by
+    /// [`U+FFFD REPLACEMENT CHARACTER`][char::REPLACEMENT_CHARACTER].
+    #[inline]
+    pub fn from_byte_slice<T: AsRef<[u8]>>(bytes: T) -> Result<Self> {
+        let bytes = bytes.as_ref();
+
+        if bytes.len() < 32 {
+            return Err(Error::InvalidHashWTxLength(bytes.len()));
+        }
+
+        let mut txid: [u8; 32] = bytes[0..32].try_into().unwrap();
+        txid.reverse();
+
+        Ok(Self {
+            txid: Txid::from_byte_array(txid),
+            wallet: String::from_utf8_lossy(&bytes[32..]).into_owned(),
+        })
+    }
+
+    /// Serializes a [`HashWTx`] to bytes.
+    #[inline]
+    pub fn serialize_to_vec(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(self.raw_length());
+
+        let mut txid = self.txid.to_byte_array();
+        txid.reverse();
+        ret.extend_from_slice(&txid);
+
+        ret.extend_from_slice(self.wallet.as_bytes());
+
+        ret
+    }
+}
+
+impl TryFrom<Vec<u8>> for HashWTx {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(value: Vec<u8>) -> Result<Self> {
+        Self::from_byte_slice(value)
+    }
+}
+
+impl TryFrom<&[u8]> for HashWTx {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(value: &[u8]) -> Result<Self> {
+        Self::from_byte_slice(value)
+    }
+}
+
+impl From<HashWTx> for Vec<u8> {
+    #[inline]
+    fn from(hash_wtx: HashWTx) -> Self {
+        hash_wtx.serialize_to_vec()
+    }
+}
+
+impl fmt::Display for HashWTx {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}, wallet={}", self.txid, self.wallet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, HashWTx};
+    use bitcoin::{hashes::Hash, Txid};
+
+    #[test]
+    fn serialization() {
+        let txid = Txid::from_byte_array(core::array::from_fn(|i| i as u8));
+        let mut txid_bytes = txid.to_byte_array();
+        txid_bytes.reverse();
+
+        for wallet in ["", "wallet", &"long wallet name ".repeat(10), "wállet ✓"] {
+            let hash_wtx = HashWTx {
+                txid,
+                wallet: wallet.to_owned(),
+            };
+            let bytes = hash_wtx.serialize_to_vec();
+            assert_eq!(hash_wtx.raw_length(), bytes.len());
+            assert_eq!(&bytes[..32], txid_bytes);
+            assert_eq!(&bytes[32..], wallet.as_bytes());
+            assert_eq!(hash_wtx, bytes.try_into().unwrap());
+        }
+
+        assert!(matches!(
+            HashWTx::from_byte_slice([0; 31]),
+            Err(Error::InvalidHashWTxLength(31))
+        ));
+        assert!(matches!(
+            HashWTx::from_byte_slice([]),
+            Err(Error::InvalidHashWTxLength(0))
+        ));
+    }
+}
@@ -0,0 +1,397 @@
+use crate::{
+    block::{GhostBlock, GhostBlockHeader, GHOST_HEADER_LEN},
+    error::Result,
+    transaction::{GhostTransaction, TxOutput, COMMITMENT_LEN, GHOST_TX_VERSION},
+};
+use bitcoin::{
+    consensus::{deserialize, encode, Decodable},
+    hashes::Hash,
+    BlockHash, Txid, Wtxid,
+};
+use core::fmt;
+use std::io::{self, Write};
+
+/// A `rawblock` of which only the header is decoded. Transactions are decoded one at a time when
+/// iterating over [`LazyBlock::transactions`], and their txids can be computed without decoding
+/// them at all. Useful when only the header, the height or a few transactions of a block are
+/// needed, decoding a [`GhostBlock`] allocates every transaction in it.
+///
+/// ```no_run
+/// use ghostcore_zmq::{LazyBlock, SubscriberBuilder, Topic};
+///
+/// let rx = SubscriberBuilder::new(&["tcp://127.0.0.1:28332"])
+///     .topics(&[Topic::RawBlock])
+///     .subscribe_raw_receiver()
+///     .unwrap();
+///
+/// for msg in rx {
+///     let block = LazyBlock::new(msg.unwrap().data).unwrap();
+///
+///     println!("block {} at height {:?}", block.block_hash(), block.height());
+///     for tx in block.transactions() {
+///         println!("  tx {}", tx.unwrap().txid());
+///     }
+/// }
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LazyBlock {
+    bytes: Vec<u8>,
+    header: GhostBlockHeader,
+    tx_count: u64,
+    txdata_start: usize,
+}
+
+impl LazyBlock {
+    /// Decodes the header and the number of transactions of the serialized block in `bytes`.
+    /// Anything after that is only checked when it is accessed.
+    pub fn new(bytes: Vec<u8>) -> Result<Self> {
+        let mut reader = bytes.as_slice();
+        let header = GhostBlockHeader::consensus_decode(&mut reader)?;
+        let tx_count = encode::VarInt::consensus_decode(&mut reader)?.0;
+        let txdata_start = bytes.len() - reader.len();
+
+        Ok(Self {
+            bytes,
+            header,
+            tx_count,
+            txdata_start,
+        })
+    }
+
+    /// Returns the header of this block.
+    #[inline]
+    pub fn header(&self) -> &GhostBlockHeader {
+        &self.header
+    }
+
+    /// Returns the hash of this block's header.
+    #[inline]
+    pub fn block_hash(&self) -> BlockHash {
+        BlockHash::hash(&self.bytes[..GHOST_HEADER_LEN])
+    }
+
+    /// Returns the number of transactions in this block, as stated before the transactions.
+    #[inline]
+    pub fn tx_count(&self) -> u64 {
+        self.tx_count
+    }
+
+    /// Returns an iterator over the transactions of this block. Every transaction is checked
+    /// when it is reached, the iterator ends after the first error.
+    #[inline]
+    pub fn transactions(&self) -> LazyTransactions<'_> {
+        LazyTransactions {
+            bytes: &self.bytes[self.txdata_start..],
+            remaining: self.tx_count,
+        }
+    }
+
+    /// Returns the height of this block as stored in its coinbase or coinstake transaction (see
+    /// [`GhostTransaction::height`]). Only the first transaction is decoded.
+    pub fn height(&self) -> Option<u32> {
+        self.transactions().next()?.ok()?.decode().ok()?.height()
+    }
+
+    /// Decodes the whole block.
+    #[inline]
+    pub fn decode(&self) -> Result<GhostBlock> {
+        Ok(deserialize(&self.bytes)?)
+    }
+
+    /// Returns the serialized block.
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the serialized block, consuming this [`LazyBlock`].
+    #[inline]
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl TryFrom<Vec<u8>> for LazyBlock {
+    type Error = crate::Error;
+
+    #[inline]
+    fn try_from(bytes: Vec<u8>) -> Result<Self> {
+        Self::new(bytes)
+    }
+}
+
+impl fmt::Display for LazyBlock {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "LazyBlock({}, {} transactions)",
+            self.block_hash(),
+            self.tx_count
+        )
+    }
+}
+
+/// Iterator returned by [`LazyBlock::transactions`].
+#[derive(Debug, Clone)]
+pub struct LazyTransactions<'a> {
+    bytes: &'a [u8],
+    remaining: u64,
+}
+
+impl<'a> Iterator for LazyTransactions<'a> {
+    type Item = Result<LazyTransaction<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        match scan_transaction(self.bytes, &mut io::sink()) {
+            Ok(len) => {
+                let (tx, rest) = self.bytes.split_at(len);
+                self.bytes = rest;
+                self.remaining -= 1;
+                Some(Ok(LazyTransaction { bytes: tx }))
+            }
+            Err(err) => {
+                self.remaining = 0;
+                Some(Err(err.into()))
+            }
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, usize::try_from(self.remaining).ok())
+    }
+}
+
+/// A transaction of a [`LazyBlock`] that has been checked but not decoded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LazyTransaction<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> LazyTransaction<'a> {
+    /// Returns the type of this transaction, see [`GhostTransaction::tx_type`].
+    #[inline]
+    pub fn tx_type(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    /// Returns true if this is a coinbase transaction.
+    #[inline]
+    pub fn is_coinbase(&self) -> bool {
+        self.tx_type() == GhostTransaction::TYPE_COINBASE
+    }
+
+    /// Returns true if this is a coinstake transaction.
+    #[inline]
+    pub fn is_coinstake(&self) -> bool {
+        self.tx_type() == GhostTransaction::TYPE_COINSTAKE
+    }
+
+    /// Computes the txid of this transaction without decoding it, the same as
+    /// [`GhostTransaction::txid`].
+    pub fn txid(&self) -> Txid {
+        let mut engine = Txid::engine();
+        scan_transaction(self.bytes, &mut engine).expect("checked when iterating");
+        Txid::from_engine(engine)
+    }
+
+    /// Computes the wtxid of this transaction without decoding it, the same as
+    /// [`GhostTransaction::wtxid`].
+    #[inline]
+    pub fn wtxid(&self) -> Wtxid {
+        Wtxid::hash(self.bytes)
+    }
+
+    /// Decodes this transaction.
+    #[inline]
+    pub fn decode(&self) -> Result<GhostTransaction> {
+        Ok(deserialize(self.bytes)?)
+    }
+
+    /// Returns the serialized transaction.
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// Reads the parts of a serialized transaction without decoding them.
+struct Scanner<'a, W> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Receives the serialization the txid commits to, everything up to `hashed` is written.
+    txid_writer: &'a mut W,
+    hashed: usize,
+}
+
+impl<W: Write> Scanner<'_, W> {
+    fn skip(&mut self, len: u64) -> core::result::Result<(), encode::Error> {
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        if len > self.bytes.len() - self.pos {
+            return Err(encode::Error::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        self.pos += len;
+        Ok(())
+    }
+
+    fn byte(&mut self) -> core::result::Result<u8, encode::Error> {
+        self.skip(1)?;
+        Ok(self.bytes[self.pos - 1])
+    }
+
+    fn var_int(&mut self) -> core::result::Result<u64, encode::Error> {
+        let mut reader = &self.bytes[self.pos..];
+        let n = encode::VarInt::consensus_decode(&mut reader)?.0;
+        self.pos = self.bytes.len() - reader.len();
+        Ok(n)
+    }
+
+    fn skip_vec(&mut self) -> core::result::Result<(), encode::Error> {
+        let len = self.var_int()?;
+        self.skip(len)
+    }
+
+    /// Writes everything read since the last call to the txid writer.
+    fn hash(&mut self) {
+        self.txid_writer
+            .write_all(&self.bytes[self.hashed..self.pos])
+            .expect("engines don't error");
+        self.hashed = self.pos;
+    }
+
+    /// Skips a range proof, which the txid commits to as an empty vector.
+    fn skip_range_proof(&mut self) -> core::result::Result<(), encode::Error> {
+        self.hash();
+        self.skip_vec()?;
+        self.txid_writer
+            .write_all(&[0])
+            .expect("engines don't error");
+        self.hashed = self.pos;
+        Ok(())
+    }
+}
+
+/// Checks the transaction at the start of `bytes` the same way decoding a [`GhostTransaction`]
+/// does and returns its length. The serialization the txid commits to is written to
+/// `txid_writer`, see [`GhostTransaction::txid`].
+fn scan_transaction<W: Write>(
+    bytes: &[u8],
+    txid_writer: &mut W,
+) -> core::result::Result<usize, encode::Error> {
+    let mut s = Scanner {
+        bytes,
+        pos: 0,
+        txid_writer,
+        hashed: 0,
+    };
+
+    if s.byte()? < GHOST_TX_VERSION {
+        return Err(encode::Error::ParseFailed(
+            "transaction version is not a Ghost transaction version",
+        ));
+    }
+    // type and lock time
+    s.skip(1 + 4)?;
+
+    let input_len = s.var_int()?;
+    for _ in 0..input_len {
+        // previous output, script and sequence
+        s.skip(32 + 4)?;
+        s.skip_vec()?;
+        s.skip(4)?;
+    }
+
+    let output_len = s.var_int()?;
+    for _ in 0..output_len {
+        match s.byte()? {
+            TxOutput::TYPE_STANDARD => {
+                s.skip(8)?;
+                s.skip_vec()?;
+            }
+            TxOutput::TYPE_CT => {
+                s.skip(COMMITMENT_LEN as u64)?;
+                s.skip_vec()?;
+                s.skip_vec()?;
+                s.skip_range_proof()?;
+            }
+            TxOutput::TYPE_RINGCT => {
+                s.skip(2 * COMMITMENT_LEN as u64)?;
+                s.skip_vec()?;
+                s.skip_range_proof()?;
+            }
+            TxOutput::TYPE_DATA => s.skip_vec()?,
+            _ => {
+                return Err(encode::Error::ParseFailed(
+                    "unknown transaction output type",
+                ))
+            }
+        }
+    }
+    s.hash();
+
+    for _ in 0..input_len {
+        let witness_len = s.var_int()?;
+        for _ in 0..witness_len {
+            s.skip_vec()?;
+        }
+    }
+
+    Ok(s.pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{block::tests::sample_block, Error, LazyBlock};
+    use bitcoin::{consensus::serialize, Witness};
+
+    #[test]
+    fn lazy_block() {
+        let mut block = sample_block();
+        let mut tx = block.txdata[0].clone();
+        tx.tx_type = 0;
+        tx.input[0].witness = Witness::new();
+        tx.output.truncate(2);
+        block.txdata.push(tx);
+
+        let bytes = serialize(&block);
+        let lazy = LazyBlock::new(bytes.clone()).unwrap();
+        assert_eq!(lazy.header(), &block.header);
+        assert_eq!(lazy.block_hash(), block.block_hash());
+        assert_eq!(lazy.tx_count(), 2);
+        assert_eq!(lazy.height(), Some(100));
+        assert_eq!(lazy.decode().unwrap(), block);
+
+        let txs: Vec<_> = lazy.transactions().map(Result::unwrap).collect();
+        assert_eq!(txs.len(), 2);
+        for (lazy_tx, tx) in txs.iter().zip(&block.txdata) {
+            // the sample transaction has range proofs and a witness, which the txid skips
+            assert_eq!(lazy_tx.txid(), tx.txid());
+            assert_eq!(lazy_tx.wtxid(), tx.wtxid());
+            assert_eq!(&lazy_tx.decode().unwrap(), tx);
+            assert_eq!(lazy_tx.tx_type(), tx.tx_type);
+        }
+        assert!(txs[0].is_coinstake());
+
+        // the block signature is not needed to read the transactions
+        let lazy = LazyBlock::new(bytes[..bytes.len() - 72].to_vec()).unwrap();
+        assert_eq!(lazy.transactions().filter(Result::is_ok).count(), 2);
+        assert!(matches!(
+            lazy.decode(),
+            Err(Error::BitcoinDeserialization(_))
+        ));
+
+        // a truncated transaction ends the iterator with an error
+        let lazy = LazyBlock::new(bytes[..bytes.len() - 100].to_vec()).unwrap();
+        let mut txs = lazy.transactions();
+        assert!(txs.next().unwrap().is_ok());
+        assert!(txs.next().unwrap().is_err());
+        assert!(txs.next().is_none());
+
+        assert!(LazyBlock::new(bytes[..100].to_vec()).is_err());
+    }
+}
@@ -1,27 +1,95 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 
+mod adapter;
+mod block;
+mod capture;
+mod chain_tracker;
+mod dedup;
 mod error;
+mod hash_wtx;
+mod lazy_block;
+mod mempool;
 mod message;
 mod monitor;
+mod publish;
+mod raw_message;
+mod received;
 mod sequence_message;
+mod sequence_tracker;
 mod subscribe;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod topic;
+mod transaction;
+mod watchdog;
 
 pub use crate::{
+    block::{GhostBlock, GhostBlockHeader, GHOST_BLOCK_VERSION, GHOST_HEADER_LEN},
+    capture::{
+        recorder::Recorder,
+        replay::{ReplaySpeed, Replayer},
+        tap::CaptureTap,
+        CaptureReader, CaptureRecord, CaptureWriter, CAPTURE_MAGIC, CAPTURE_VERSION,
+    },
+    chain_tracker::{ChainEvent, ChainTracker},
+    dedup::{DedupEvent, DedupKey, Deduplicated, Deduplicator, Delivery},
     error::Error,
+    hash_wtx::HashWTx,
+    lazy_block::{LazyBlock, LazyTransaction, LazyTransactions},
+    mempool::{
+        checker::{MempoolCheck, MempoolSequenceChecker, ResyncReason},
+        mirror::{MempoolChange, MempoolMirror, RemovalReason},
+    },
     message::{Message, DATA_MAX_LEN, SEQUENCE_LEN, TOPIC_MAX_LEN},
     monitor::{
         event::{HandshakeFailure, SocketEvent},
-        MonitorMessage,
+        handshake::{EndpointReport, EndpointState, HandshakeError, HandshakeReport, Timeout},
+        state::{
+            ConnectionEvent, ConnectionState, ConnectionTracked, ConnectionTracker,
+            ConnectionTransition,
+        },
+        MonitorMessage, SocketMessage,
     },
+    publish::Publisher,
+    raw_message::RawMessage,
+    received::Received,
     sequence_message::SequenceMessage,
-    subscribe::{blocking::subscribe_blocking, receiver::subscribe_receiver},
+    sequence_tracker::{SequenceEvent, SequenceTracked, SequenceTracker, TrackedMessage},
+    subscribe::{
+        blocking::{
+            subscribe_blocking, subscribe_blocking_handler, subscribe_blocking_monitor,
+            subscribe_blocking_timeout, subscribe_blocking_topics, subscribe_blocking_with_source,
+            subscribe_raw_blocking, subscribe_raw_blocking_handler, subscribe_raw_blocking_monitor,
+            subscribe_raw_blocking_timeout, subscribe_raw_blocking_with_source, Handler,
+            SubscribeEvent,
+        },
+        bounded::{BoundedIntoIter, BoundedReceiver, OverflowPolicy},
+        builder::SubscriberBuilder,
+        handle::SubscriptionHandle,
+        receiver::{
+            subscribe_raw_receiver, subscribe_raw_receiver_bounded, subscribe_raw_receiver_monitor,
+            subscribe_raw_receiver_with_handle, subscribe_raw_receiver_with_source,
+            subscribe_raw_wait_handshake, subscribe_receiver, subscribe_receiver_bounded,
+            subscribe_receiver_monitor, subscribe_receiver_topics, subscribe_receiver_with_handle,
+            subscribe_receiver_with_source, subscribe_wait_handshake, subscribe_watchdog,
+        },
+    },
+    topic::Topic,
+    transaction::{
+        GhostTransaction, OutputCT, OutputData, OutputRingCT, OutputStandard, TxOutput,
+        COMMITMENT_LEN, GHOST_TX_VERSION,
+    },
+    watchdog::{Watchdog, WatchdogEvent, Watched},
 };
 
 #[cfg(feature = "async")]
 pub use crate::subscribe::stream::{
     subscribe_async, subscribe_async_monitor, subscribe_async_monitor_stream,
     subscribe_async_stream::{self, MessageStream},
-    subscribe_async_wait_handshake, subscribe_async_wait_handshake_timeout, SocketMessage,
+    subscribe_async_topics, subscribe_async_wait_handshake, subscribe_async_wait_handshake_timeout,
+    subscribe_async_with_source, subscribe_raw_async, subscribe_raw_async_monitor,
+    subscribe_raw_async_wait_handshake, subscribe_raw_async_wait_handshake_timeout,
+    subscribe_raw_async_with_source, RawMessageStream, SourceMessageStream,
 };
 
 #[allow(deprecated)]
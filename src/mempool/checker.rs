@@ -0,0 +1,366 @@
+use crate::{message::Message, sequence_message::SequenceMessage};
+use bitcoin::BlockHash;
+use core::fmt;
+
+/// The reason a [`MempoolSequenceChecker`] requires a resync.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResyncReason {
+    /// The ZMQ sequence number of the `sequence` topic skipped one or more messages.
+    MissedMessages { expected: u32, got: u32 },
+    /// The ZMQ sequence number of the `sequence` topic went back to 0, the publisher restarted.
+    PublisherRestart { last: u32 },
+    /// The mempool sequence skipped one or more values without a block being connected or
+    /// disconnected in between.
+    MempoolSequenceGap { expected: u64, got: u64 },
+    /// The mempool sequence went back to a value that was already seen, for example because the
+    /// node restarted.
+    MempoolSequenceWentBack { expected: u64, got: u64 },
+    /// The `rawblock` message of a connected block was not received, so the transactions it
+    /// confirmed are still in the mempool. Only reported by a
+    /// [`MempoolMirror`][crate::MempoolMirror].
+    MissedBlock { blockhash: BlockHash },
+}
+
+impl fmt::Display for ResyncReason {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissedMessages { expected, got } => {
+                write!(
+                    f,
+                    "missed messages (expected sequence {expected}, got {got})"
+                )
+            }
+            Self::PublisherRestart { last } => {
+                write!(f, "publisher restarted (last sequence {last})")
+            }
+            Self::MempoolSequenceGap { expected, got } => {
+                write!(f, "mempool sequence gap (expected {expected}, got {got})")
+            }
+            Self::MempoolSequenceWentBack { expected, got } => write!(
+                f,
+                "mempool sequence went back (expected {expected}, got {got})"
+            ),
+            Self::MissedBlock { blockhash } => write!(f, "missed rawblock of block {blockhash}"),
+        }
+    }
+}
+
+/// The result of checking a [`SequenceMessage`] with a [`MempoolSequenceChecker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MempoolCheck {
+    /// The message is the next expected one and should be applied.
+    Apply,
+    /// The message is already included in the mempool passed to
+    /// [`MempoolSequenceChecker::resynced`] and should be ignored.
+    Stale,
+    /// Notifications were missed, the message should be ignored and the mempool has to be
+    /// resynced using `getrawmempool` with `mempool_sequence` set to `true`. This is returned once,
+    /// following messages are [`MempoolCheck::Unsynced`] until
+    /// [`MempoolSequenceChecker::resynced`] is called.
+    ResyncRequired(ResyncReason),
+    /// A resync is still pending, the message should be ignored.
+    Unsynced,
+}
+
+impl MempoolCheck {
+    /// Returns true if the message should be applied.
+    #[inline]
+    pub fn should_apply(&self) -> bool {
+        matches!(self, Self::Apply)
+    }
+}
+
+/// Checks the continuity of the `mempool_sequence` of [`SequenceMessage`]s to detect missed
+/// mempool notifications.
+///
+/// Bitcoin Core increments the mempool sequence on every transaction added to or removed from the
+/// mempool, so every [`MempoolAcceptance`] and [`MempoolRemoval`] message should have the next
+/// mempool sequence. The exception are transactions removed because they were included in a block,
+/// these do not produce a [`MempoolRemoval`] message, so the mempool sequence is allowed to jump
+/// forward after a [`BlockConnect`] or [`BlockDisconnect`]. The ZMQ sequence number of the
+/// `sequence` topic is checked as well, a lost block message would otherwise go unnoticed.
+///
+/// To (re)sync, subscribe first, then call `getrawmempool` with `mempool_sequence` set to `true`
+/// and pass the returned mempool sequence to [`MempoolSequenceChecker::resynced`]. Notifications
+/// older than that are reported as [`MempoolCheck::Stale`].
+///
+/// ```
+/// use ghostcore_zmq::{MempoolCheck, MempoolSequenceChecker, SequenceMessage};
+/// use bitcoin::{hashes::Hash, Txid};
+///
+/// let mut checker = MempoolSequenceChecker::new();
+/// let accept = |mempool_sequence| SequenceMessage::MempoolAcceptance {
+///     txid: Txid::all_zeros(),
+///     mempool_sequence,
+/// };
+///
+/// assert_eq!(checker.check(&accept(10), 0), MempoolCheck::Apply);
+/// assert_eq!(checker.check(&accept(11), 1), MempoolCheck::Apply);
+/// assert!(matches!(
+///     checker.check(&accept(15), 2),
+///     MempoolCheck::ResyncRequired(_)
+/// ));
+/// assert_eq!(checker.check(&accept(16), 3), MempoolCheck::Unsynced);
+///
+/// // getrawmempool returned mempool_sequence 17
+/// checker.resynced(17);
+/// assert_eq!(checker.check(&accept(16), 4), MempoolCheck::Stale);
+/// assert_eq!(checker.check(&accept(17), 5), MempoolCheck::Apply);
+/// ```
+///
+/// [`MempoolAcceptance`]: SequenceMessage::MempoolAcceptance
+/// [`MempoolRemoval`]: SequenceMessage::MempoolRemoval
+/// [`BlockConnect`]: SequenceMessage::BlockConnect
+/// [`BlockDisconnect`]: SequenceMessage::BlockDisconnect
+#[derive(Debug, Clone)]
+pub struct MempoolSequenceChecker {
+    last_sequence: Option<u32>,
+    next_mempool_sequence: Option<u64>,
+    stale_below: Option<u64>,
+    block_since_last: bool,
+    synced: bool,
+}
+
+impl Default for MempoolSequenceChecker {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MempoolSequenceChecker {
+    /// Creates a new [`MempoolSequenceChecker`]. The first mempool message is accepted as is, use
+    /// [`MempoolSequenceChecker::new_unsynced`] if the mempool is synced with `getrawmempool` first.
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            last_sequence: None,
+            next_mempool_sequence: None,
+            stale_below: None,
+            block_since_last: false,
+            synced: true,
+        }
+    }
+
+    /// Creates a new [`MempoolSequenceChecker`] that returns [`MempoolCheck::Unsynced`] until
+    /// [`MempoolSequenceChecker::resynced`] is called.
+    #[inline]
+    pub fn new_unsynced() -> Self {
+        Self {
+            synced: false,
+            ..Self::new()
+        }
+    }
+
+    /// Returns true if no resync is pending.
+    #[inline]
+    pub fn is_synced(&self) -> bool {
+        self.synced
+    }
+
+    /// Returns the mempool sequence the next [`MempoolAcceptance`] or [`MempoolRemoval`] is
+    /// expected to have, if known. This may be higher after a block is connected or disconnected.
+    ///
+    /// [`MempoolAcceptance`]: SequenceMessage::MempoolAcceptance
+    /// [`MempoolRemoval`]: SequenceMessage::MempoolRemoval
+    #[inline]
+    pub fn next_mempool_sequence(&self) -> Option<u64> {
+        self.next_mempool_sequence
+    }
+
+    /// Marks the mempool as resynced. `mempool_sequence` is the mempool sequence returned by
+    /// `getrawmempool`, mempool messages with a lower mempool sequence are already included in
+    /// its result.
+    #[inline]
+    pub fn resynced(&mut self, mempool_sequence: u64) {
+        self.next_mempool_sequence = Some(mempool_sequence);
+        self.stale_below = Some(mempool_sequence);
+        self.block_since_last = false;
+        self.synced = true;
+    }
+
+    /// Checks a [`SequenceMessage`] with the ZMQ sequence number it was received with.
+    pub fn check(&mut self, message: &SequenceMessage, sequence: u32) -> MempoolCheck {
+        if let Some(reason) = self.check_sequence(sequence) {
+            return self.require_resync(reason);
+        }
+
+        let Some(mempool_sequence) = message.mempool_sequence() else {
+            // BlockConnect or BlockDisconnect
+            self.block_since_last = true;
+            return self.synced_or(MempoolCheck::Apply);
+        };
+
+        if self
+            .stale_below
+            .is_some_and(|stale| mempool_sequence < stale)
+        {
+            return self.synced_or(MempoolCheck::Stale);
+        }
+        self.stale_below = None;
+
+        let Some(expected) = self.next_mempool_sequence else {
+            self.accept(mempool_sequence);
+            return self.synced_or(MempoolCheck::Apply);
+        };
+
+        if mempool_sequence < expected {
+            return self.require_resync(ResyncReason::MempoolSequenceWentBack {
+                expected,
+                got: mempool_sequence,
+            });
+        }
+
+        if mempool_sequence > expected && !self.block_since_last {
+            self.accept(mempool_sequence);
+            return self.require_resync(ResyncReason::MempoolSequenceGap {
+                expected,
+                got: mempool_sequence,
+            });
+        }
+
+        self.accept(mempool_sequence);
+
+        self.synced_or(MempoolCheck::Apply)
+    }
+
+    /// Same as [`MempoolSequenceChecker::check`], returning [`None`] for messages that are not
+    /// [`Message::Sequence`].
+    #[inline]
+    pub fn check_message(&mut self, message: &Message) -> Option<MempoolCheck> {
+        match message {
+            Message::Sequence(sm, sequence) => Some(self.check(sm, *sequence)),
+            _ => None,
+        }
+    }
+
+    fn check_sequence(&mut self, sequence: u32) -> Option<ResyncReason> {
+        let last = self.last_sequence.replace(sequence)?;
+        let expected = last.wrapping_add(1);
+
+        if sequence == expected {
+            None
+        } else if sequence == 0 {
+            Some(ResyncReason::PublisherRestart { last })
+        } else {
+            Some(ResyncReason::MissedMessages {
+                expected,
+                got: sequence,
+            })
+        }
+    }
+
+    fn accept(&mut self, mempool_sequence: u64) {
+        self.next_mempool_sequence = Some(mempool_sequence + 1);
+        self.block_since_last = false;
+    }
+
+    pub(super) fn require_resync(&mut self, reason: ResyncReason) -> MempoolCheck {
+        if !self.synced {
+            return MempoolCheck::Unsynced;
+        }
+
+        self.synced = false;
+
+        MempoolCheck::ResyncRequired(reason)
+    }
+
+    fn synced_or(&self, check: MempoolCheck) -> MempoolCheck {
+        if self.synced {
+            check
+        } else {
+            MempoolCheck::Unsynced
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MempoolCheck, MempoolSequenceChecker, ResyncReason, SequenceMessage};
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
+
+    fn accept(mempool_sequence: u64) -> SequenceMessage {
+        SequenceMessage::MempoolAcceptance {
+            txid: Txid::all_zeros(),
+            mempool_sequence,
+        }
+    }
+
+    fn remove(mempool_sequence: u64) -> SequenceMessage {
+        SequenceMessage::MempoolRemoval {
+            txid: Txid::all_zeros(),
+            mempool_sequence,
+        }
+    }
+
+    fn connect() -> SequenceMessage {
+        SequenceMessage::BlockConnect {
+            blockhash: BlockHash::all_zeros(),
+        }
+    }
+
+    #[test]
+    fn continuity() {
+        let mut checker = MempoolSequenceChecker::new();
+
+        assert_eq!(checker.check(&accept(1), 0), MempoolCheck::Apply);
+        assert_eq!(checker.check(&accept(2), 1), MempoolCheck::Apply);
+        assert_eq!(checker.check(&remove(3), 2), MempoolCheck::Apply);
+        // 2 transactions were included in the block
+        assert_eq!(checker.check(&connect(), 3), MempoolCheck::Apply);
+        assert_eq!(checker.check(&accept(6), 4), MempoolCheck::Apply);
+        assert_eq!(checker.next_mempool_sequence(), Some(7));
+
+        assert_eq!(
+            checker.check(&accept(8), 5),
+            MempoolCheck::ResyncRequired(ResyncReason::MempoolSequenceGap {
+                expected: 7,
+                got: 8
+            })
+        );
+        assert!(!checker.is_synced());
+        assert_eq!(checker.check(&accept(9), 6), MempoolCheck::Unsynced);
+        assert_eq!(checker.check(&connect(), 7), MempoolCheck::Unsynced);
+
+        checker.resynced(10);
+        assert!(checker.is_synced());
+        assert_eq!(checker.check(&remove(9), 8), MempoolCheck::Stale);
+        assert_eq!(checker.check(&remove(10), 9), MempoolCheck::Apply);
+
+        assert_eq!(
+            checker.check(&remove(10), 10),
+            MempoolCheck::ResyncRequired(ResyncReason::MempoolSequenceWentBack {
+                expected: 11,
+                got: 10
+            })
+        );
+    }
+
+    #[test]
+    fn zmq_sequence() {
+        let mut checker = MempoolSequenceChecker::new();
+
+        assert_eq!(checker.check(&accept(1), 5), MempoolCheck::Apply);
+        // a lost BlockConnect can not be distinguished from lost mempool messages
+        assert_eq!(
+            checker.check(&accept(4), 7),
+            MempoolCheck::ResyncRequired(ResyncReason::MissedMessages {
+                expected: 6,
+                got: 7
+            })
+        );
+
+        checker.resynced(5);
+        assert_eq!(checker.check(&accept(5), 8), MempoolCheck::Apply);
+        assert_eq!(
+            checker.check(&connect(), 0),
+            MempoolCheck::ResyncRequired(ResyncReason::PublisherRestart { last: 8 })
+        );
+
+        let mut checker = MempoolSequenceChecker::new_unsynced();
+        assert_eq!(checker.check(&accept(1), 0), MempoolCheck::Unsynced);
+        checker.resynced(2);
+        assert_eq!(checker.check(&accept(2), 1), MempoolCheck::Apply);
+    }
+}
@@ -0,0 +1,459 @@
+use super::checker::{MempoolCheck, MempoolSequenceChecker, ResyncReason};
+use crate::{
+    block::GhostBlock, message::Message, sequence_message::SequenceMessage,
+    transaction::GhostTransaction,
+};
+use bitcoin::{BlockHash, Txid};
+use core::fmt;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+/// Maximum number of `rawtx` messages kept while waiting for the matching
+/// [`MempoolAcceptance`][SequenceMessage::MempoolAcceptance].
+const UNMATCHED_CAPACITY: usize = 100;
+
+/// Maximum number of `rawblock` messages remembered while waiting for the matching
+/// [`BlockConnect`][SequenceMessage::BlockConnect].
+const EARLY_BLOCKS_CAPACITY: usize = 8;
+
+/// Why a transaction was removed from a [`MempoolMirror`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// A [`MempoolRemoval`][SequenceMessage::MempoolRemoval] was received, the transaction was
+    /// replaced, evicted, expired or conflicted with another transaction.
+    Removed,
+    /// The transaction was included in the block with this hash.
+    Confirmed(BlockHash),
+}
+
+/// A change of the contents of a [`MempoolMirror`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MempoolChange {
+    Added(Txid),
+    Removed {
+        txid: Txid,
+        reason: RemovalReason,
+    },
+    /// Notifications were missed, the mirror is out of date until [`MempoolMirror::resync`] is
+    /// called.
+    ResyncRequired(ResyncReason),
+    /// The contents of the mirror were replaced by [`MempoolMirror::resync`].
+    Resynced,
+}
+
+impl fmt::Display for MempoolChange {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Added(txid) => write!(f, "Added({txid})"),
+            Self::Removed {
+                txid,
+                reason: RemovalReason::Removed,
+            } => write!(f, "Removed({txid})"),
+            Self::Removed {
+                txid,
+                reason: RemovalReason::Confirmed(blockhash),
+            } => write!(f, "Confirmed({txid}, block={blockhash})"),
+            Self::ResyncRequired(reason) => write!(f, "ResyncRequired({reason})"),
+            Self::Resynced => f.write_str("Resynced"),
+        }
+    }
+}
+
+/// An in-memory copy of the mempool of a node, driven by ZMQ notifications.
+///
+/// Transactions are added and removed by messages from the `sequence` topic. Transactions
+/// included in a block do not produce a [`MempoolRemoval`], subscribe to the `rawblock` topic as
+/// well (on the same endpoint) to remove them. A [`BlockConnect`] whose `rawblock` message is not
+/// received leaves its transactions in the mirror, so it is reported as
+/// [`ResyncReason::MissedBlock`] when the next block is connected. If full transactions are
+/// stored (see [`MempoolMirror::with_transactions`]), they are taken from the `rawtx` topic.
+///
+/// Mempool sequence numbers are checked with a [`MempoolSequenceChecker`]. The mirror starts
+/// empty, only containing transactions added after subscribing, until [`MempoolMirror::resync`]
+/// is called with the result of `getrawmempool`. The same has to be done after a
+/// [`MempoolChange::ResyncRequired`].
+///
+/// ```no_run
+/// use ghostcore_zmq::{subscribe_receiver_topics, MempoolMirror, Topic};
+///
+/// let rx = subscribe_receiver_topics(
+///     &["tcp://127.0.0.1:28332"],
+///     &[Topic::Sequence, Topic::RawBlock],
+/// )
+/// .unwrap();
+///
+/// let mut mempool = MempoolMirror::new();
+///
+/// for msg in rx {
+///     match msg {
+///         Ok(msg) => {
+///             for change in mempool.apply(&msg) {
+///                 println!("{change}, mempool size: {}", mempool.len());
+///             }
+///         }
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+/// }
+/// ```
+///
+/// [`MempoolRemoval`]: SequenceMessage::MempoolRemoval
+/// [`BlockConnect`]: SequenceMessage::BlockConnect
+#[derive(Debug, Default)]
+pub struct MempoolMirror {
+    txs: HashMap<Txid, Option<GhostTransaction>>,
+    checker: MempoolSequenceChecker,
+    store_transactions: bool,
+    unmatched: HashMap<Txid, GhostTransaction>,
+    /// Txids of `unmatched` in the order they were received, may contain txids that were matched
+    /// already.
+    unmatched_order: VecDeque<Txid>,
+    /// A connected block of which the `rawblock` message was not received yet.
+    pending_block: Option<BlockHash>,
+    /// Blocks of which the `rawblock` message was received before their `BlockConnect`.
+    early_blocks: VecDeque<BlockHash>,
+    senders: Vec<Sender<MempoolChange>>,
+}
+
+impl MempoolMirror {
+    /// Creates a new, empty [`MempoolMirror`] that only stores txids.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new, empty [`MempoolMirror`] that also stores the transactions received on the
+    /// `rawtx` topic.
+    #[inline]
+    pub fn with_transactions() -> Self {
+        Self {
+            store_transactions: true,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of transactions in the mempool.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.txs.len()
+    }
+
+    /// Returns true if the mempool is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.txs.is_empty()
+    }
+
+    /// Returns true if the transaction with this txid is in the mempool.
+    #[inline]
+    pub fn contains(&self, txid: &Txid) -> bool {
+        self.txs.contains_key(txid)
+    }
+
+    /// Returns the transaction with this txid, if it is in the mempool and its `rawtx` message was
+    /// received.
+    #[inline]
+    pub fn get(&self, txid: &Txid) -> Option<&GhostTransaction> {
+        self.txs.get(txid)?.as_ref()
+    }
+
+    /// Returns an iterator over the txids in the mempool, in arbitrary order.
+    #[inline]
+    pub fn txids(&self) -> impl Iterator<Item = &Txid> {
+        self.txs.keys()
+    }
+
+    /// Returns an iterator over the txids in the mempool together with their transaction if it is
+    /// known, in arbitrary order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&Txid, Option<&GhostTransaction>)> {
+        self.txs.iter().map(|(txid, tx)| (txid, tx.as_ref()))
+    }
+
+    /// Returns true if no resync is pending.
+    #[inline]
+    pub fn is_synced(&self) -> bool {
+        self.checker.is_synced()
+    }
+
+    /// Returns the [`MempoolSequenceChecker`] used to check mempool sequence numbers.
+    #[inline]
+    pub fn checker(&self) -> &MempoolSequenceChecker {
+        &self.checker
+    }
+
+    /// Returns a [`Receiver`] that receives every change made to this mempool from now on.
+    #[inline]
+    pub fn subscribe_changes(&mut self) -> Receiver<MempoolChange> {
+        let (tx, rx) = channel();
+        self.senders.push(tx);
+        rx
+    }
+
+    /// Replaces the contents of this mempool by the txids returned by `getrawmempool` (with
+    /// `mempool_sequence` set to `true`). Transactions that were already known are kept.
+    pub fn resync<I: IntoIterator<Item = Txid>>(&mut self, txids: I, mempool_sequence: u64) {
+        let mut old = core::mem::take(&mut self.txs);
+        self.txs = txids
+            .into_iter()
+            .map(|txid| (txid, old.remove(&txid).flatten()))
+            .collect();
+
+        self.checker.resynced(mempool_sequence);
+        self.pending_block = None;
+
+        self.notify(&[MempoolChange::Resynced]);
+    }
+
+    /// Applies a [`Message`], returning the changes made to this mempool. Messages other than
+    /// `sequence`, `rawtx` and `rawblock` are ignored.
+    pub fn apply(&mut self, message: &Message) -> Vec<MempoolChange> {
+        let mut changes = Vec::new();
+
+        match message {
+            Message::Sequence(sm, sequence) => match self.checker.check(sm, *sequence) {
+                MempoolCheck::Apply => self.apply_sequence(sm, &mut changes),
+                MempoolCheck::ResyncRequired(reason) => {
+                    changes.push(MempoolChange::ResyncRequired(reason))
+                }
+                MempoolCheck::Stale | MempoolCheck::Unsynced => {}
+            },
+            Message::Tx(tx, _) => self.apply_tx(tx),
+            Message::Block(block, _) => self.apply_block(block, &mut changes),
+            Message::HashBlock(..) | Message::HashTx(..) | Message::HashWTx(..) => {}
+        }
+
+        self.notify(&changes);
+
+        changes
+    }
+
+    fn apply_sequence(&mut self, sm: &SequenceMessage, changes: &mut Vec<MempoolChange>) {
+        match *sm {
+            SequenceMessage::MempoolAcceptance { txid, .. } => {
+                let tx = self.unmatched.remove(&txid);
+
+                if self.txs.insert(txid, tx).is_none() {
+                    changes.push(MempoolChange::Added(txid));
+                }
+            }
+            SequenceMessage::MempoolRemoval { txid, .. } => {
+                if self.txs.remove(&txid).is_some() {
+                    changes.push(MempoolChange::Removed {
+                        txid,
+                        reason: RemovalReason::Removed,
+                    });
+                }
+            }
+            SequenceMessage::BlockConnect { blockhash } => {
+                if let Some(index) = self.early_blocks.iter().position(|&b| b == blockhash) {
+                    self.early_blocks.remove(index);
+                } else if let Some(missed) = self.pending_block.replace(blockhash) {
+                    let reason = ResyncReason::MissedBlock { blockhash: missed };
+
+                    if let MempoolCheck::ResyncRequired(reason) =
+                        self.checker.require_resync(reason)
+                    {
+                        changes.push(MempoolChange::ResyncRequired(reason));
+                    }
+                }
+            }
+            // transactions of a disconnected block that return to the mempool produce a
+            // `MempoolAcceptance`
+            SequenceMessage::BlockDisconnect { .. } => {}
+        }
+    }
+
+    fn apply_tx(&mut self, tx: &GhostTransaction) {
+        if !self.store_transactions {
+            return;
+        }
+
+        match self.txs.get_mut(&tx.txid()) {
+            Some(slot @ None) => *slot = Some(tx.clone()),
+            Some(Some(_)) => {}
+            None => {
+                // the sequence message may arrive later
+                let txid = tx.txid();
+                if self.unmatched.insert(txid, tx.clone()).is_some() {
+                    return;
+                }
+                self.unmatched_order.push_back(txid);
+
+                while self.unmatched.len() > UNMATCHED_CAPACITY {
+                    if let Some(oldest) = self.unmatched_order.pop_front() {
+                        self.unmatched.remove(&oldest);
+                    }
+                }
+                if self.unmatched_order.len() > 2 * UNMATCHED_CAPACITY {
+                    let unmatched = &self.unmatched;
+                    self.unmatched_order
+                        .retain(|txid| unmatched.contains_key(txid));
+                }
+            }
+        }
+    }
+
+    fn apply_block(&mut self, block: &GhostBlock, changes: &mut Vec<MempoolChange>) {
+        let blockhash = block.block_hash();
+
+        if self.pending_block == Some(blockhash) {
+            self.pending_block = None;
+        } else {
+            if self.early_blocks.len() == EARLY_BLOCKS_CAPACITY {
+                self.early_blocks.pop_front();
+            }
+            self.early_blocks.push_back(blockhash);
+        }
+
+        for tx in &block.txdata {
+            let txid = tx.txid();
+
+            if self.txs.remove(&txid).is_some() {
+                changes.push(MempoolChange::Removed {
+                    txid,
+                    reason: RemovalReason::Confirmed(blockhash),
+                });
+            }
+        }
+    }
+
+    fn notify(&mut self, changes: &[MempoolChange]) {
+        if changes.is_empty() {
+            return;
+        }
+
+        self.senders.retain(|sender| {
+            changes
+                .iter()
+                .all(|change| sender.send(change.clone()).is_ok())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        block::tests::sample_block, transaction::tests::sample_transaction, MempoolChange,
+        MempoolMirror, Message, RemovalReason, ResyncReason, SequenceMessage,
+    };
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
+
+    fn connect(blockhash: BlockHash, sequence: u32) -> Message {
+        Message::Sequence(SequenceMessage::BlockConnect { blockhash }, sequence)
+    }
+
+    #[test]
+    fn mirror() {
+        let tx = sample_transaction();
+        let txid = tx.txid();
+        let other = Txid::from_byte_array([1; 32]);
+
+        let mut mempool = MempoolMirror::with_transactions();
+        let changes = mempool.subscribe_changes();
+
+        // rawtx before the sequence message
+        assert_eq!(mempool.apply(&Message::Tx(tx.clone(), 0)), []);
+        assert_eq!(
+            mempool.apply(&Message::Sequence(
+                SequenceMessage::MempoolAcceptance {
+                    txid,
+                    mempool_sequence: 1
+                },
+                0
+            )),
+            [MempoolChange::Added(txid)]
+        );
+        assert_eq!(mempool.get(&txid), Some(&tx));
+
+        mempool.apply(&Message::Sequence(
+            SequenceMessage::MempoolAcceptance {
+                txid: other,
+                mempool_sequence: 2,
+            },
+            1,
+        ));
+        assert_eq!(mempool.len(), 2);
+        assert!(mempool.contains(&other));
+        assert_eq!(mempool.get(&other), None);
+
+        let block = sample_block();
+        assert_eq!(
+            mempool.apply(&Message::Block(block.clone(), 0)),
+            [MempoolChange::Removed {
+                txid,
+                reason: RemovalReason::Confirmed(block.block_hash())
+            }]
+        );
+
+        assert_eq!(
+            mempool.apply(&Message::Sequence(
+                SequenceMessage::MempoolRemoval {
+                    txid: other,
+                    mempool_sequence: 5
+                },
+                2
+            )),
+            [MempoolChange::ResyncRequired(
+                ResyncReason::MempoolSequenceGap {
+                    expected: 3,
+                    got: 5
+                }
+            )]
+        );
+        assert!(!mempool.is_synced());
+
+        mempool.resync([other, txid], 6);
+        assert!(mempool.is_synced());
+        assert_eq!(mempool.len(), 2);
+
+        assert_eq!(
+            changes.try_iter().collect::<Vec<_>>(),
+            [
+                MempoolChange::Added(txid),
+                MempoolChange::Added(other),
+                MempoolChange::Removed {
+                    txid,
+                    reason: RemovalReason::Confirmed(block.block_hash())
+                },
+                MempoolChange::ResyncRequired(ResyncReason::MempoolSequenceGap {
+                    expected: 3,
+                    got: 5
+                }),
+                MempoolChange::Resynced,
+            ]
+        );
+    }
+
+    #[test]
+    fn missed_block() {
+        let block = sample_block();
+        let blockhash = block.block_hash();
+        let other = BlockHash::from_byte_array([1; 32]);
+
+        let mut mempool = MempoolMirror::new();
+
+        // rawblock after and before its BlockConnect
+        assert_eq!(mempool.apply(&connect(blockhash, 0)), []);
+        assert_eq!(mempool.apply(&Message::Block(block.clone(), 0)), []);
+        assert_eq!(mempool.apply(&Message::Block(block.clone(), 1)), []);
+        assert_eq!(mempool.apply(&connect(blockhash, 1)), []);
+        assert!(mempool.is_synced());
+
+        // the rawblock of `other` never arrives
+        assert_eq!(mempool.apply(&connect(other, 2)), []);
+        assert_eq!(
+            mempool.apply(&connect(blockhash, 3)),
+            [MempoolChange::ResyncRequired(ResyncReason::MissedBlock {
+                blockhash: other
+            })]
+        );
+        assert!(!mempool.is_synced());
+
+        mempool.resync([], 10);
+        assert!(mempool.is_synced());
+        assert_eq!(mempool.apply(&connect(other, 4)), []);
+    }
+}
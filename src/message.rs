@@ -1,11 +1,15 @@
 use crate::{
+    block::GhostBlock,
     error::{Error, Result},
+    hash_wtx::HashWTx,
     sequence_message::SequenceMessage,
+    topic::Topic,
+    transaction::GhostTransaction,
 };
 use bitcoin::{
     consensus::{deserialize, serialize},
     hashes::Hash,
-    Block, BlockHash, Transaction, Txid, Weight,
+    BlockHash, Txid, Weight,
 };
 use core::{cmp::min, fmt};
 
@@ -17,9 +21,9 @@ pub const SEQUENCE_LEN: usize = 4;
 pub enum Message {
     HashBlock(BlockHash, u32),
     HashTx(Txid, u32),
-    HashWTx(Txid, String, u32),
-    Block(Block, u32),
-    Tx(Transaction, u32),
+    HashWTx(HashWTx, u32),
+    Block(GhostBlock, u32),
+    Tx(GhostTransaction, u32),
     Sequence(SequenceMessage, u32),
 }
 
@@ -33,53 +37,36 @@ impl Message {
     /// Returns the topic of this [`Message`] as a string slice.
     #[inline]
     pub fn topic_str(&self) -> &'static str {
-        let topic = match self {
-            Self::HashBlock(..) => "hashblock",
-            Self::HashTx(..) => "hashtx",
-            Self::HashWTx(..) => "hashwtx",
-            Self::Block(..) => "rawblock",
-            Self::Tx(..) => "rawtx",
-            Self::Sequence(..) => "sequence",
-        };
-
-        debug_assert!(topic.len() <= TOPIC_MAX_LEN);
-
-        topic
+        self.topic_type().as_str()
+    }
+
+    /// Returns the topic of this [`Message`] as a [`Topic`].
+    #[inline]
+    pub fn topic_type(&self) -> Topic {
+        match self {
+            Self::HashBlock(..) => Topic::HashBlock,
+            Self::HashTx(..) => Topic::HashTx,
+            Self::HashWTx(..) => Topic::HashWTx,
+            Self::Block(..) => Topic::RawBlock,
+            Self::Tx(..) => Topic::RawTx,
+            Self::Sequence(..) => Topic::Sequence,
+        }
     }
 
     /// Serializes the middle part of this [`Message`] (no topic and sequence).
     #[inline]
     pub fn serialize_data_to_vec(&self) -> Vec<u8> {
         match self {
-            Self::HashBlock(_, _) | Self::HashTx(_, _) | Self::HashWTx(_, _, _) => {
-                let arr = match self {
+            Self::HashBlock(_, _) | Self::HashTx(_, _) => {
+                let mut arr = match self {
                     Self::HashBlock(blockhash, _) => blockhash.to_byte_array(),
                     Self::HashTx(txid, _) => txid.to_byte_array(),
-                    Self::HashWTx(txid, wallet, _) => {
-                        let mut arr = txid.to_byte_array();
-                        arr.reverse();
-
-                        // Convert wallet string to bytes
-                        let wallet_bytes = wallet.as_bytes();
-
-                        // Check if wallet_bytes is less than 32 bytes and pad with zeros if necessary
-                        let mut padded_wallet: [u8; 32] = [0; 32];
-                        padded_wallet[..wallet_bytes.len()].copy_from_slice(wallet_bytes);
-
-                        // Concatenate the arrays
-                        let mut result = Vec::new();
-                        result.extend_from_slice(&arr);
-                        result.extend_from_slice(&padded_wallet);
-
-                        // Convert Vec<u8> to [u8; 32]
-                        result.try_into().unwrap_or_else(|v: Vec<u8>| {
-                            panic!("Expected Vec<u8> with length 32, found length {}", v.len())
-                        })
-                    }
                     _ => unreachable!(),
                 };
+                arr.reverse();
                 arr.to_vec()
             }
+            Self::HashWTx(hash_wtx, _) => hash_wtx.serialize_to_vec(),
             Self::Block(block, _) => serialize(&block),
             Self::Tx(tx, _) => serialize(&tx),
             Self::Sequence(sm, _) => sm.serialize_to_vec(),
@@ -103,7 +90,7 @@ impl Message {
         match self {
             Self::HashBlock(_, seq)
             | Self::HashTx(_, seq)
-            | Self::HashWTx(_, _, seq)
+            | Self::HashWTx(_, seq)
             | Self::Block(_, seq)
             | Self::Tx(_, seq)
             | Self::Sequence(_, seq) => *seq,
@@ -153,20 +140,7 @@ impl Message {
             b"rawblock" => Self::Block(deserialize(data)?, seq),
             b"rawtx" => Self::Tx(deserialize(data)?, seq),
             b"sequence" => Self::Sequence(SequenceMessage::from_byte_slice(data)?, seq),
-            b"hashwtx" => {
-                let mut txid_bytes: [u8; 32] = data[..32]
-                    .try_into()
-                    .map_err(|_| Error::Invalid256BitHashLength(data.len()))?;
-                txid_bytes.reverse();
-                let wallet_bytes: Vec<u8> = data[32..].to_vec();
-                let txid: Txid = Txid::from_byte_array(txid_bytes);
-
-                Self::HashWTx(
-                    txid,
-                    String::from_utf8_lossy(&wallet_bytes).into_owned(),
-                    seq,
-                )
-            }
+            b"hashwtx" => Self::HashWTx(HashWTx::from_byte_slice(data)?, seq),
             _ => {
                 let mut buf = [0; TOPIC_MAX_LEN];
 
@@ -217,9 +191,7 @@ impl fmt::Display for Message {
         match self {
             Self::HashBlock(blockhash, seq) => write!(f, "HashBlock({blockhash}, sequence={seq})"),
             Self::HashTx(txid, seq) => write!(f, "HashTx({txid}, sequence={seq})"),
-            Self::HashWTx(txid, wallet, seq) => {
-                write!(f, "HashWTx({txid}, wallet={wallet}, sequence={seq})")
-            }
+            Self::HashWTx(hash_wtx, seq) => write!(f, "HashWTx({hash_wtx}, sequence={seq})"),
             Self::Block(block, seq) => write!(f, "Block({}, sequence={seq})", block.block_hash()),
             Self::Tx(tx, seq) => write!(f, "Tx({}, sequence={seq})", tx.txid()),
             Self::Sequence(sm, seq) => write!(f, "Sequence({sm}, sequence={seq})"),
@@ -229,18 +201,46 @@ impl fmt::Display for Message {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Error, Message};
+    use crate::{
+        block::tests::sample_block, transaction::tests::sample_transaction, Error, HashWTx,
+        Message, Topic,
+    };
     use bitcoin::{consensus::serialize, constants::genesis_block, hashes::Hash, Network};
 
     #[test]
-    fn test_deserialize_rawtx() {
-        let genesis_block = genesis_block(Network::Bitcoin);
+    fn hash_byte_order() {
+        // the node publishes hashes in the order they are displayed (and used by RPC), which is
+        // the reverse of their internal byte order
+        let genesis = genesis_block(Network::Bitcoin);
+        let hex = |bytes: &[u8]| bytes.iter().map(|b| format!("{b:02x}")).collect::<String>();
+
+        let msg = Message::HashBlock(genesis.block_hash(), 0);
+        let data = msg.serialize_data_to_vec();
+        assert_eq!(
+            hex(&data),
+            "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f"
+        );
+        assert_eq!(
+            Message::from_multipart(&[b"hashblock".as_slice(), &data, &[0; 4]]).unwrap(),
+            msg
+        );
 
-        let tx = &genesis_block.txdata[0];
+        let msg = Message::HashTx(genesis.txdata[0].txid(), 0);
+        let data = msg.serialize_data_to_vec();
+        assert_eq!(
+            hex(&data),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(
+            Message::from_multipart(&[b"hashtx".as_slice(), &data, &[0; 4]]).unwrap(),
+            msg
+        );
+    }
+
+    #[test]
+    fn test_deserialize_rawtx() {
+        let tx = &sample_transaction();
         let tx_bytes = serialize(tx);
-        let txid = tx.txid();
-        let mut txid_bytes = txid.to_byte_array();
-        txid_bytes.reverse();
 
         let to_deserialize = [
             b"rawtx" as &[u8],
@@ -277,6 +277,33 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_deserialize_rawblock() {
+        let block = sample_block();
+        let block_bytes = serialize(&block);
+
+        let to_deserialize = [
+            b"rawblock" as &[u8],
+            &block_bytes,
+            &[0x02, 0x00, 0x00, 0x00],
+        ];
+
+        let msg = Message::from_multipart(&to_deserialize).unwrap();
+
+        assert_eq!(msg, Message::Block(block.clone(), 2));
+
+        assert_eq!(msg.topic_str(), "rawblock");
+        assert_eq!(msg.topic_type(), Topic::RawBlock);
+        assert_eq!(msg.serialize_data_to_vec(), block_bytes);
+        assert_eq!(msg.sequence(), 2);
+        assert_eq!(
+            msg.to_string(),
+            format!("Block({}, sequence=2)", block.block_hash())
+        );
+
+        assert_eq!(msg.serialize_to_vecs(), to_deserialize);
+    }
+
     #[test]
     fn test_deserialize_hashtx() {
         let genesis_block = genesis_block(Network::Bitcoin);
@@ -298,6 +325,54 @@ mod tests {
         assert_eq!(msg.serialize_to_vecs(), to_deserialize);
     }
 
+    #[test]
+    fn test_deserialize_hashwtx() {
+        let genesis_block = genesis_block(Network::Bitcoin);
+
+        let txid = genesis_block.txdata[0].txid();
+        let mut txid_bytes = txid.to_byte_array();
+        txid_bytes.reverse();
+
+        let wallet = "a wallet name that is longer than 32 bytes";
+        let data = [&txid_bytes as &[u8], wallet.as_bytes()].concat();
+
+        let to_deserialize = [b"hashwtx" as &[u8], &data, &[0x05, 0x00, 0x00, 0x00]];
+
+        let msg = Message::from_multipart(&to_deserialize).unwrap();
+
+        assert_eq!(
+            msg,
+            Message::HashWTx(
+                HashWTx {
+                    txid,
+                    wallet: wallet.to_owned()
+                },
+                5
+            )
+        );
+
+        assert_eq!(msg.topic_str(), "hashwtx");
+        assert_eq!(msg.serialize_data_to_vec(), data);
+        assert_eq!(msg.sequence(), 5);
+
+        assert_eq!(msg.serialize_to_vecs(), to_deserialize);
+
+        // the default wallet has an empty name
+        let to_deserialize = [b"hashwtx" as &[u8], &txid_bytes, &[0x06, 0x00, 0x00, 0x00]];
+        let msg = Message::from_multipart(&to_deserialize).unwrap();
+        assert_eq!(
+            msg,
+            Message::HashWTx(
+                HashWTx {
+                    txid,
+                    wallet: String::new()
+                },
+                6
+            )
+        );
+        assert_eq!(msg.serialize_to_vecs(), to_deserialize);
+    }
+
     #[test]
     fn test_deserialization_error_mp_len() {
         let to_deserialize = [
@@ -381,5 +456,10 @@ mod tests {
             Message::from_multipart(&[b"sequence" as &[u8], &[0; 32], &[0x0c, 0x00, 0x00, 0x00]]),
             Err(Error::InvalidSequenceMessageLength(32))
         ));
+
+        assert!(matches!(
+            Message::from_multipart(&[b"hashwtx" as &[u8], &[0; 31], &[0x0d, 0x00, 0x00, 0x00]]),
+            Err(Error::InvalidHashWTxLength(31))
+        ));
     }
 }
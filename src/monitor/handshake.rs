@@ -0,0 +1,479 @@
+use super::{
+    event::{HandshakeFailure, SocketEvent},
+    MonitorMessage,
+};
+use core::{fmt, time::Duration};
+use std::net::{SocketAddr, ToSocketAddrs};
+
+/// Why a ZMTP handshake failed, see [`SocketEvent::HandshakeFailedNoDetail`],
+/// [`SocketEvent::HandshakeFailedProtocol`] and [`SocketEvent::HandshakeFailedAuth`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeError {
+    NoDetail,
+    Protocol(HandshakeFailure),
+    /// Authentication failed, with the ZAP status code.
+    Auth(u32),
+}
+
+impl HandshakeError {
+    /// Returns the [`HandshakeError`] of a handshake failure event, or [`None`] if the event is
+    /// not a handshake failure.
+    pub fn from_event(event: &SocketEvent) -> Option<Self> {
+        Some(match *event {
+            SocketEvent::HandshakeFailedNoDetail { .. } => Self::NoDetail,
+            SocketEvent::HandshakeFailedProtocol { err } => Self::Protocol(err),
+            SocketEvent::HandshakeFailedAuth { error_code } => Self::Auth(error_code),
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoDetail => f.write_str("handshake failed"),
+            Self::Protocol(failure) => write!(f, "handshake failed: protocol error {failure:?}"),
+            Self::Auth(status_code) => {
+                write!(f, "handshake failed: authentication error {status_code}")
+            }
+        }
+    }
+}
+
+/// The state of the connection to a single endpoint, as far as it can be derived from the events
+/// of a socket's monitor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndpointState {
+    /// No connection attempt has completed yet.
+    Connecting,
+    /// A connection attempt failed, ZMQ tries again after `interval`.
+    Retrying { interval: Duration },
+    /// The connection is established, but the ZMTP handshake has not completed yet.
+    Connected,
+    /// The ZMTP handshake succeeded, messages can be received.
+    HandshakeSucceeded,
+    /// The ZMTP handshake failed.
+    HandshakeFailed(HandshakeError),
+    /// The connection was lost, ZMQ reconnects automatically.
+    Disconnected,
+    /// The socket's monitor stopped, the state is not tracked anymore.
+    MonitorStopped,
+}
+
+impl EndpointState {
+    /// Returns the state an endpoint is in after `event`, or [`None`] if the event does not
+    /// change the state of a connection (like [`SocketEvent::Closed`]).
+    pub fn from_event(event: &SocketEvent) -> Option<Self> {
+        Some(match *event {
+            SocketEvent::ConnectDelayed => Self::Connecting,
+            SocketEvent::ConnectRetried { interval } => Self::Retrying {
+                interval: Duration::from_millis(interval.into()),
+            },
+            SocketEvent::Connected { .. } => Self::Connected,
+            SocketEvent::HandshakeSucceeded => Self::HandshakeSucceeded,
+            SocketEvent::Disconnected { .. } => Self::Disconnected,
+            SocketEvent::MonitorStopped => Self::MonitorStopped,
+            ref event => Self::HandshakeFailed(HandshakeError::from_event(event)?),
+        })
+    }
+}
+
+impl fmt::Display for EndpointState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connecting => f.write_str("connecting"),
+            Self::Retrying { interval } => write!(f, "retrying every {interval:?}"),
+            Self::Connected => f.write_str("connected, handshake pending"),
+            Self::HandshakeSucceeded => f.write_str("handshake succeeded"),
+            Self::HandshakeFailed(err) => write!(f, "{err}"),
+            Self::Disconnected => f.write_str("disconnected"),
+            Self::MonitorStopped => f.write_str("monitor stopped"),
+        }
+    }
+}
+
+/// The state of a single endpoint in a [`HandshakeReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EndpointReport {
+    pub endpoint: String,
+    pub state: EndpointState,
+    /// The last handshake failure. A failed handshake is followed by a disconnect and a new
+    /// connection attempt, so the state alone does not show it.
+    pub handshake_failure: Option<HandshakeError>,
+}
+
+impl fmt::Display for EndpointReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.endpoint, self.state)?;
+        match self.handshake_failure {
+            Some(err) if !matches!(self.state, EndpointState::HandshakeFailed(_)) => {
+                write!(f, " (last {err})")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The state of every endpoint while waiting for the handshakes to complete, see
+/// [`subscribe_wait_handshake`][crate::subscribe_wait_handshake].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HandshakeReport {
+    endpoints: Vec<EndpointReport>,
+    /// For every entry of `endpoints`, the index of the configured endpoint it belongs to. The
+    /// first `configured` entries are the configured endpoints themselves.
+    owners: Vec<Option<usize>>,
+    configured: usize,
+}
+
+impl HandshakeReport {
+    pub(crate) fn new<'a, I: IntoIterator<Item = &'a str>>(endpoints: I) -> Self {
+        let endpoints: Vec<_> = endpoints
+            .into_iter()
+            .map(|endpoint| EndpointReport {
+                endpoint: endpoint.to_owned(),
+                state: EndpointState::Connecting,
+                handshake_failure: None,
+            })
+            .collect();
+
+        Self {
+            owners: (0..endpoints.len()).map(Some).collect(),
+            configured: endpoints.len(),
+            endpoints,
+        }
+    }
+
+    /// Updates the state of the event's endpoint.
+    pub(crate) fn on_event(&mut self, msg: &MonitorMessage) {
+        let state = match EndpointState::from_event(&msg.event) {
+            // not related to a single endpoint
+            None | Some(EndpointState::MonitorStopped) => return,
+            Some(state) => state,
+        };
+
+        let index = match self
+            .endpoints
+            .iter()
+            .position(|report| report.endpoint == msg.source_url)
+        {
+            Some(index) => index,
+            None => {
+                // ZMQ reports the handshake under the resolved address instead of the one used
+                // when connecting
+                let owner = self.resolved_owner(&msg.source_url);
+                self.endpoints.push(EndpointReport {
+                    endpoint: msg.source_url.clone(),
+                    state: EndpointState::Connecting,
+                    handshake_failure: None,
+                });
+                self.owners.push(owner);
+                self.endpoints.len() - 1
+            }
+        };
+        let report = &mut self.endpoints[index];
+
+        report.state = state;
+        match state {
+            EndpointState::HandshakeSucceeded => report.handshake_failure = None,
+            EndpointState::HandshakeFailed(err) => report.handshake_failure = Some(err),
+            _ => {}
+        }
+    }
+
+    /// Returns true if the handshake with every configured endpoint succeeded, under the address
+    /// it was configured with or under the address it resolved to.
+    pub fn is_complete(&self) -> bool {
+        (0..self.configured).all(|configured| self.succeeded(configured))
+    }
+
+    /// Returns the state of every endpoint.
+    #[inline]
+    pub fn endpoints(&self) -> &[EndpointReport] {
+        &self.endpoints
+    }
+
+    /// Returns the state of `endpoint`.
+    pub fn get(&self, endpoint: &str) -> Option<&EndpointReport> {
+        self.endpoints
+            .iter()
+            .find(|report| report.endpoint == endpoint)
+    }
+
+    /// Returns the endpoints the handshake has not succeeded with. An endpoint whose handshake
+    /// succeeded under its resolved address is not pending, neither under the address it was
+    /// configured with nor under other resolved addresses.
+    pub fn pending(&self) -> impl Iterator<Item = &EndpointReport> {
+        self.endpoints
+            .iter()
+            .zip(&self.owners)
+            .filter(|(report, owner)| match owner {
+                Some(configured) => !self.succeeded(*configured),
+                None => report.state != EndpointState::HandshakeSucceeded,
+            })
+            .map(|(report, _)| report)
+    }
+
+    /// Returns true if the handshake with the configured endpoint at index `configured` succeeded
+    /// under any of its addresses.
+    fn succeeded(&self, configured: usize) -> bool {
+        self.endpoints
+            .iter()
+            .zip(&self.owners)
+            .any(|(report, owner)| {
+                *owner == Some(configured) && report.state == EndpointState::HandshakeSucceeded
+            })
+    }
+
+    /// Returns the index of the configured endpoint that `address`, an address reported by ZMQ,
+    /// was resolved from. Endpoints are matched by transport and port, then by host, then by
+    /// resolving their host names. Returns [`None`] if no single endpoint matches.
+    fn resolved_owner(&self, address: &str) -> Option<usize> {
+        let (transport, host, port) = split_endpoint(address)?;
+
+        let candidates: Vec<_> = self.endpoints[..self.configured]
+            .iter()
+            .enumerate()
+            .filter_map(|(index, report)| {
+                let (t, h, p) = split_endpoint(&report.endpoint)?;
+                (t == transport && p == port).then_some((index, h))
+            })
+            .collect();
+
+        let single = |matches: Vec<usize>| match matches[..] {
+            [index] => Some(index),
+            _ => None,
+        };
+
+        if let [(index, _)] = candidates[..] {
+            return Some(index);
+        }
+        if let Some(index) = single(
+            candidates
+                .iter()
+                .filter(|(_, h)| *h == host)
+                .map(|(index, _)| *index)
+                .collect(),
+        ) {
+            return Some(index);
+        }
+
+        let address: SocketAddr = format!("{host}:{port}").parse().ok()?;
+        single(
+            candidates
+                .iter()
+                .filter(|(_, h)| {
+                    format!("{h}:{port}")
+                        .to_socket_addrs()
+                        .is_ok_and(|mut addrs| addrs.any(|addr| addr == address))
+                })
+                .map(|(index, _)| *index)
+                .collect(),
+        )
+    }
+}
+
+/// Splits an endpoint like `tcp://127.0.0.1:28332` into its transport, host and port. A source
+/// address (`tcp://eth0;127.0.0.1:28332`) is skipped. Returns [`None`] for endpoints without a
+/// port, like `ipc://` and `inproc://` endpoints.
+fn split_endpoint(endpoint: &str) -> Option<(&str, &str, &str)> {
+    let (transport, address) = endpoint.split_once("://")?;
+    let address = address
+        .rsplit_once(';')
+        .map_or(address, |(_, address)| address);
+    let (host, port) = address.rsplit_once(':')?;
+
+    (!port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        .then_some((transport, host, port))
+}
+
+impl fmt::Display for HandshakeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, report) in self.endpoints.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{report}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`subscribe_wait_handshake`][crate::subscribe_wait_handshake] (and its async
+/// counterpart) when the connection times out. Contains a [`HandshakeReport`] with the state of
+/// every endpoint at the time of the timeout.
+#[derive(Debug)]
+pub struct Timeout(HandshakeReport);
+
+impl Timeout {
+    #[inline]
+    pub(crate) fn new(report: HandshakeReport) -> Self {
+        Self(report)
+    }
+
+    /// Returns the state of every endpoint at the time of the timeout.
+    #[inline]
+    pub fn report(&self) -> &HandshakeReport {
+        &self.0
+    }
+
+    /// Returns the state of every endpoint at the time of the timeout.
+    #[inline]
+    pub fn into_report(self) -> HandshakeReport {
+        self.0
+    }
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection timed out (")?;
+        for (i, report) in self.0.pending().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{report}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl std::error::Error for Timeout {}
+
+#[cfg(test)]
+mod tests {
+    use super::{EndpointState, HandshakeError, HandshakeReport};
+    use crate::{adapter::tests::monitor_event, HandshakeFailure, SocketEvent};
+    use core::time::Duration;
+
+    #[test]
+    fn report() {
+        let mut report = HandshakeReport::new(["tcp://a", "tcp://b"]);
+        assert!(!report.is_complete());
+
+        report.on_event(&monitor_event("tcp://a", SocketEvent::ConnectDelayed));
+        report.on_event(&monitor_event("tcp://a", SocketEvent::Connected { fd: 3 }));
+        report.on_event(&monitor_event("tcp://a", SocketEvent::HandshakeSucceeded));
+
+        report.on_event(&monitor_event("tcp://b", SocketEvent::Connected { fd: 4 }));
+        report.on_event(&monitor_event(
+            "tcp://b",
+            SocketEvent::HandshakeFailedProtocol {
+                err: HandshakeFailure::ZmtpMechanismMismatch,
+            },
+        ));
+        report.on_event(&monitor_event(
+            "tcp://b",
+            SocketEvent::Disconnected { fd: 4 },
+        ));
+        report.on_event(&monitor_event(
+            "tcp://b",
+            SocketEvent::ConnectRetried { interval: 100 },
+        ));
+
+        assert!(!report.is_complete());
+        assert_eq!(
+            report.get("tcp://a").unwrap().state,
+            EndpointState::HandshakeSucceeded
+        );
+
+        let b = report.get("tcp://b").unwrap();
+        assert_eq!(
+            b.state,
+            EndpointState::Retrying {
+                interval: Duration::from_millis(100)
+            }
+        );
+        assert_eq!(
+            b.handshake_failure,
+            Some(HandshakeError::Protocol(
+                HandshakeFailure::ZmtpMechanismMismatch
+            ))
+        );
+        assert_eq!(report.pending().count(), 1);
+        assert_eq!(
+            report.to_string(),
+            "tcp://a: handshake succeeded, tcp://b: retrying every 100ms (last handshake failed: \
+             protocol error ZmtpMechanismMismatch)"
+        );
+
+        report.on_event(&monitor_event("tcp://b", SocketEvent::HandshakeSucceeded));
+        assert!(report.is_complete());
+        assert_eq!(report.get("tcp://b").unwrap().handshake_failure, None);
+    }
+
+    #[test]
+    fn resolved_address() {
+        let mut report = HandshakeReport::new(["tcp://localhost:1", "tcp://b"]);
+
+        report.on_event(&monitor_event(
+            "tcp://127.0.0.1:1",
+            SocketEvent::HandshakeSucceeded,
+        ));
+        assert_eq!(
+            report
+                .pending()
+                .map(|report| &*report.endpoint)
+                .collect::<Vec<_>>(),
+            ["tcp://b"]
+        );
+        // the same endpoint under both addresses only counts once
+        report.on_event(&monitor_event(
+            "tcp://localhost:1",
+            SocketEvent::HandshakeSucceeded,
+        ));
+        assert!(!report.is_complete());
+
+        report.on_event(&monitor_event("tcp://b", SocketEvent::HandshakeSucceeded));
+        assert!(report.is_complete());
+        assert_eq!(report.pending().count(), 0);
+        assert_eq!(report.endpoints().len(), 3);
+
+        // an address without a port can not be matched
+        let mut report = HandshakeReport::new(["tcp://a"]);
+        report.on_event(&monitor_event("tcp://a", SocketEvent::Connected { fd: 3 }));
+        report.on_event(&monitor_event("tcp://c", SocketEvent::HandshakeSucceeded));
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn resolved_address_port() {
+        let mut report = HandshakeReport::new(["tcp://localhost:1", "tcp://localhost:2"]);
+
+        // endpoints are matched by port, not by the order of the events
+        report.on_event(&monitor_event(
+            "tcp://127.0.0.1:2",
+            SocketEvent::HandshakeSucceeded,
+        ));
+        report.on_event(&monitor_event(
+            "tcp://127.0.0.1:1",
+            SocketEvent::HandshakeFailedNoDetail { fd: 3 },
+        ));
+        assert!(!report.is_complete());
+        assert_eq!(
+            report
+                .pending()
+                .map(|report| &*report.endpoint)
+                .collect::<Vec<_>>(),
+            ["tcp://localhost:1", "tcp://127.0.0.1:1"]
+        );
+
+        report.on_event(&monitor_event(
+            "tcp://127.0.0.1:1",
+            SocketEvent::HandshakeSucceeded,
+        ));
+        assert!(report.is_complete());
+
+        // with the same port, the host is resolved
+        let mut report = HandshakeReport::new(["tcp://192.0.2.1:3", "tcp://localhost:3"]);
+        report.on_event(&monitor_event(
+            "tcp://127.0.0.1:3",
+            SocketEvent::HandshakeSucceeded,
+        ));
+        assert_eq!(
+            report
+                .pending()
+                .map(|report| &*report.endpoint)
+                .collect::<Vec<_>>(),
+            ["tcp://192.0.2.1:3"]
+        );
+    }
+}
@@ -1,5 +1,8 @@
 pub mod event;
+pub mod handshake;
+pub mod state;
 
+use crate::message::Message;
 use core::fmt;
 use event::SocketEvent;
 
@@ -23,6 +26,15 @@ impl MonitorMessage {
     }
 }
 
+/// A [`Message`] (or a [`RawMessage`][crate::RawMessage]) or a [`MonitorMessage`], produced by
+/// the subscribe functions that monitor their socket (like
+/// [`subscribe_receiver_monitor`][crate::subscribe_receiver_monitor]).
+#[derive(Debug, Clone)]
+pub enum SocketMessage<M = Message> {
+    Message(M),
+    Event(MonitorMessage),
+}
+
 #[derive(Debug)]
 // currently all variants have the same prefix: `Invalid`, which is correct and intended
 #[allow(clippy::enum_variant_names)]
@@ -0,0 +1,419 @@
+use super::{
+    handshake::{EndpointState, HandshakeError},
+    MonitorMessage, SocketMessage,
+};
+use crate::{
+    adapter::{Adapter, Process},
+    error::Result,
+    message::Message,
+};
+use core::fmt;
+use std::{collections::VecDeque, time::Instant};
+
+#[cfg(feature = "async")]
+use core::{
+    pin::Pin,
+    task::{Context as AsyncContext, Poll},
+};
+#[cfg(feature = "async")]
+use futures_util::stream::Stream;
+
+/// The state of the connection to a single endpoint, kept by a [`ConnectionTracker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionState {
+    pub state: EndpointState,
+    /// When the endpoint entered `state`.
+    pub since: Instant,
+    /// The number of connection attempts that failed since the last successful handshake.
+    pub retries: u32,
+    /// The last handshake failure, cleared when a handshake succeeds.
+    pub last_error: Option<HandshakeError>,
+    /// When the last handshake succeeded.
+    pub last_handshake: Option<Instant>,
+}
+
+impl ConnectionState {
+    fn new(now: Instant) -> Self {
+        Self {
+            state: EndpointState::Connecting,
+            since: now,
+            retries: 0,
+            last_error: None,
+            last_handshake: None,
+        }
+    }
+
+    /// Returns true if the handshake succeeded and the connection was not lost since.
+    #[inline]
+    pub fn is_connected(&self) -> bool {
+        self.state == EndpointState::HandshakeSucceeded
+    }
+}
+
+/// A change of the [`EndpointState`] of an endpoint, produced by [`ConnectionTracker`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionTransition {
+    pub endpoint: String,
+    pub from: EndpointState,
+    pub to: EndpointState,
+    pub at: Instant,
+}
+
+impl fmt::Display for ConnectionTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.endpoint, self.from, self.to)
+    }
+}
+
+/// Keeps the [`ConnectionState`] of every endpoint of a socket, derived from the events of its
+/// monitor (see [`MonitorMessage`]).
+///
+/// Endpoints are identified by the `source_url` of their events. Endpoints passed to
+/// [`ConnectionTracker::with_endpoints`] start out as [`EndpointState::Connecting`], others are
+/// added when their first event is received.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionTracker {
+    endpoints: Vec<(String, ConnectionState)>,
+}
+
+impl ConnectionTracker {
+    /// Creates a new [`ConnectionTracker`] without any endpoints.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a new [`ConnectionTracker`] with `endpoints` in the [`EndpointState::Connecting`]
+    /// state.
+    pub fn with_endpoints(endpoints: &[&str]) -> Self {
+        let now = Instant::now();
+
+        Self {
+            endpoints: endpoints
+                .iter()
+                .map(|&endpoint| (endpoint.to_owned(), ConnectionState::new(now)))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of endpoints.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Returns true if there are no endpoints.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    /// Returns the state of `endpoint`.
+    pub fn get(&self, endpoint: &str) -> Option<&ConnectionState> {
+        self.endpoints
+            .iter()
+            .find(|(e, _)| e == endpoint)
+            .map(|(_, state)| state)
+    }
+
+    /// Returns an iterator over every endpoint and its state, in the order they were added.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConnectionState)> {
+        self.endpoints
+            .iter()
+            .map(|(endpoint, state)| (endpoint.as_str(), state))
+    }
+
+    /// Returns true if every endpoint is connected (see [`ConnectionState::is_connected`]).
+    pub fn all_connected(&self) -> bool {
+        self.endpoints.iter().all(|(_, state)| state.is_connected())
+    }
+
+    /// Applies a monitor event, returning the resulting state transitions. Events that do not
+    /// change the state of a connection (like [`SocketEvent::Closed`]) are ignored.
+    ///
+    /// [`SocketEvent::Closed`]: crate::SocketEvent::Closed
+    #[inline]
+    pub fn on_event(&mut self, msg: &MonitorMessage) -> Vec<ConnectionTransition> {
+        self.on_event_at(msg, Instant::now())
+    }
+
+    /// Like [`ConnectionTracker::on_event`], but with the time the event was received.
+    pub fn on_event_at(&mut self, msg: &MonitorMessage, now: Instant) -> Vec<ConnectionTransition> {
+        let Some(to) = EndpointState::from_event(&msg.event) else {
+            return Vec::new();
+        };
+
+        if to == EndpointState::MonitorStopped {
+            // not related to a single endpoint
+            return self
+                .endpoints
+                .iter_mut()
+                .filter_map(|(endpoint, state)| transition(endpoint, state, to, now))
+                .collect();
+        }
+
+        let (endpoint, state) = match self
+            .endpoints
+            .iter()
+            .position(|(endpoint, _)| *endpoint == msg.source_url)
+        {
+            Some(index) => &mut self.endpoints[index],
+            None => {
+                self.endpoints
+                    .push((msg.source_url.clone(), ConnectionState::new(now)));
+                self.endpoints.last_mut().unwrap()
+            }
+        };
+
+        match to {
+            EndpointState::Retrying { .. } => state.retries += 1,
+            EndpointState::HandshakeSucceeded => {
+                state.retries = 0;
+                state.last_error = None;
+                state.last_handshake = Some(now);
+            }
+            EndpointState::HandshakeFailed(err) => state.last_error = Some(err),
+            _ => {}
+        }
+
+        transition(endpoint, state, to, now).into_iter().collect()
+    }
+}
+
+fn transition(
+    endpoint: &str,
+    state: &mut ConnectionState,
+    to: EndpointState,
+    now: Instant,
+) -> Option<ConnectionTransition> {
+    if state.state == to {
+        return None;
+    }
+
+    let from = state.state;
+    state.state = to;
+    state.since = now;
+
+    Some(ConnectionTransition {
+        endpoint: endpoint.to_owned(),
+        from,
+        to,
+        at: now,
+    })
+}
+
+/// An item produced by [`ConnectionTracked`].
+#[derive(Debug, Clone)]
+pub enum ConnectionEvent {
+    Message(Message),
+    Transition(ConnectionTransition),
+}
+
+/// Wraps an iterator or stream of [`SocketMessage`]s (like the [`Receiver`] returned by
+/// [`subscribe_receiver_monitor`]), passing the events to a [`ConnectionTracker`]. Messages are
+/// passed through, events are replaced by the state transitions they cause.
+///
+/// ```no_run
+/// use ghostcore_zmq::{
+///     subscribe_receiver_monitor, ConnectionEvent, ConnectionTracked, ConnectionTracker,
+/// };
+///
+/// let endpoints = ["tcp://127.0.0.1:28332"];
+/// let rx = subscribe_receiver_monitor(&endpoints).unwrap();
+///
+/// for event in ConnectionTracked::new(rx.into_iter(), ConnectionTracker::with_endpoints(&endpoints)) {
+///     match event {
+///         Ok(ConnectionEvent::Message(msg)) => println!("Received message: {msg}"),
+///         Ok(ConnectionEvent::Transition(transition)) => println!("{transition}"),
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+/// }
+/// ```
+///
+/// [`Receiver`]: std::sync::mpsc::Receiver
+/// [`subscribe_receiver_monitor`]: crate::subscribe_receiver_monitor
+#[derive(Debug)]
+pub struct ConnectionTracked<I> {
+    adapter: Adapter<I, ConnectionTracker>,
+}
+
+impl<I> ConnectionTracked<I> {
+    /// Wraps `inner` using `tracker`.
+    #[inline]
+    pub fn new(inner: I, tracker: ConnectionTracker) -> Self {
+        Self {
+            adapter: Adapter::new(inner, tracker),
+        }
+    }
+
+    /// Returns a reference to the [`ConnectionTracker`] used.
+    #[inline]
+    pub fn tracker(&self) -> &ConnectionTracker {
+        &self.adapter.processor
+    }
+
+    /// Returns the wrapped iterator or stream.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.adapter.inner
+    }
+}
+
+impl Process for ConnectionTracker {
+    type Input = SocketMessage;
+    type Output = ConnectionEvent;
+
+    fn process(&mut self, msg: SocketMessage, out: &mut VecDeque<ConnectionEvent>) {
+        match msg {
+            SocketMessage::Message(msg) => out.push_back(ConnectionEvent::Message(msg)),
+            SocketMessage::Event(event) => out.extend(
+                self.on_event(&event)
+                    .into_iter()
+                    .map(ConnectionEvent::Transition),
+            ),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<SocketMessage>>> Iterator for ConnectionTracked<I> {
+    type Item = Result<ConnectionEvent>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.adapter.next()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: Stream<Item = Result<SocketMessage>> + Unpin> Stream for ConnectionTracked<S> {
+    type Item = Result<ConnectionEvent>;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut AsyncContext<'_>) -> Poll<Option<Self::Item>> {
+        self.adapter.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        adapter::tests::monitor_event, ConnectionEvent, ConnectionTracked, ConnectionTracker,
+        EndpointState, HandshakeError, HandshakeFailure, Message, SocketEvent, SocketMessage,
+    };
+    use bitcoin::{hashes::Hash, BlockHash};
+    use core::time::Duration;
+    use std::time::Instant;
+
+    #[test]
+    fn tracker() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut tracker = ConnectionTracker::with_endpoints(&["tcp://a"]);
+
+        assert_eq!(
+            tracker.on_event_at(
+                &monitor_event("tcp://a", SocketEvent::ConnectDelayed),
+                at(1)
+            ),
+            []
+        );
+        for ms in [2, 3] {
+            tracker.on_event_at(
+                &monitor_event("tcp://a", SocketEvent::ConnectRetried { interval: 100 }),
+                at(ms),
+            );
+        }
+        let a = tracker.get("tcp://a").unwrap();
+        assert_eq!(a.retries, 2);
+        // the interval did not change, so neither did the state
+        assert_eq!(a.since, at(2));
+
+        tracker.on_event_at(
+            &monitor_event("tcp://a", SocketEvent::Connected { fd: 3 }),
+            at(4),
+        );
+        let transitions = tracker.on_event_at(
+            &monitor_event(
+                "tcp://a",
+                SocketEvent::HandshakeFailedProtocol {
+                    err: HandshakeFailure::ZmtpUnspecified,
+                },
+            ),
+            at(5),
+        );
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].from, EndpointState::Connected);
+        assert_eq!(
+            transitions[0].to.to_string(),
+            "handshake failed: protocol error ZmtpUnspecified"
+        );
+
+        tracker.on_event_at(
+            &monitor_event("tcp://a", SocketEvent::Disconnected { fd: 3 }),
+            at(6),
+        );
+        let a = tracker.get("tcp://a").unwrap();
+        assert_eq!(a.state, EndpointState::Disconnected);
+        assert_eq!(
+            a.last_error,
+            Some(HandshakeError::Protocol(HandshakeFailure::ZmtpUnspecified))
+        );
+
+        tracker.on_event_at(
+            &monitor_event("tcp://a", SocketEvent::HandshakeSucceeded),
+            at(7),
+        );
+        // unknown endpoints are added
+        tracker.on_event_at(
+            &monitor_event("tcp://b", SocketEvent::HandshakeSucceeded),
+            at(8),
+        );
+        assert!(tracker.all_connected());
+        assert_eq!(tracker.len(), 2);
+
+        let a = tracker.get("tcp://a").unwrap();
+        assert_eq!((a.retries, a.last_error), (0, None));
+        assert_eq!(a.last_handshake, Some(at(7)));
+
+        let transitions =
+            tracker.on_event_at(&monitor_event("", SocketEvent::MonitorStopped), at(9));
+        assert_eq!(transitions.len(), 2);
+        assert!(tracker
+            .iter()
+            .all(|(_, state)| state.state == EndpointState::MonitorStopped));
+    }
+
+    #[test]
+    fn tracked() {
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        let messages = [
+            SocketMessage::Event(monitor_event("tcp://a", SocketEvent::ConnectDelayed)),
+            SocketMessage::Event(monitor_event("tcp://a", SocketEvent::Connected { fd: 3 })),
+            SocketMessage::Event(monitor_event("tcp://a", SocketEvent::HandshakeSucceeded)),
+            SocketMessage::Message(msg.clone()),
+        ];
+
+        let mut tracked = ConnectionTracked::new(
+            messages.into_iter().map(Ok),
+            ConnectionTracker::with_endpoints(&["tcp://a"]),
+        );
+
+        let mut transitions = Vec::new();
+        let received = loop {
+            match tracked.next().unwrap().unwrap() {
+                ConnectionEvent::Message(received) => break received,
+                ConnectionEvent::Transition(transition) => transitions.push(transition.to),
+            }
+        };
+
+        assert_eq!(received, msg);
+        assert_eq!(
+            transitions,
+            [EndpointState::Connected, EndpointState::HandshakeSucceeded]
+        );
+        assert!(tracked.tracker().all_connected());
+        assert!(tracked.next().is_none());
+    }
+}
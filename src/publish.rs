@@ -0,0 +1,168 @@
+use crate::{error::Result, message::Message, topic::Topic};
+use zmq::{Context, Socket};
+
+/// Publishes [`Message`]s on a ZMQ PUB socket, the same way Ghost Core does. Useful for test
+/// fixtures, relays and simulators.
+///
+/// Like Ghost Core, the publisher keeps a sequence number per topic that starts at 0 and goes up
+/// by one for every message sent on that topic. [`Publisher::send`] replaces the sequence number
+/// of the message by the next one of its topic, [`Publisher::forward`] sends it unchanged.
+///
+/// ```no_run
+/// use bitcoin::{hashes::Hash, BlockHash};
+/// use ghostcore_zmq::{Message, Publisher};
+///
+/// let mut publisher = Publisher::bind(&["tcp://127.0.0.1:28332"]).unwrap();
+///
+/// // the sequence number is replaced, this is sent with sequence number 0
+/// publisher
+///     .send(&Message::HashBlock(BlockHash::all_zeros(), 0))
+///     .unwrap();
+/// ```
+pub struct Publisher {
+    socket: Socket,
+    sequences: [u32; Topic::ALL.len()],
+}
+
+fn topic_index(topic: Topic) -> usize {
+    Topic::ALL.iter().position(|&t| t == topic).unwrap()
+}
+
+impl Publisher {
+    /// Creates a PUB socket in a new [`Context`] and binds it to all endpoints.
+    #[inline]
+    pub fn bind(endpoints: &[&str]) -> Result<Self> {
+        Self::bind_with_context(&Context::new(), endpoints)
+    }
+
+    /// Creates a PUB socket in `context` and binds it to all endpoints. `inproc://` endpoints can
+    /// only be subscribed to from the same context.
+    pub fn bind_with_context(context: &Context, endpoints: &[&str]) -> Result<Self> {
+        let socket = context.socket(zmq::PUB)?;
+
+        for endpoint in endpoints {
+            socket.bind(endpoint)?;
+        }
+
+        Ok(Self::from_socket(socket))
+    }
+
+    /// Creates a publisher that sends on `socket`, which must be a PUB or XPUB socket.
+    pub(crate) fn from_socket(socket: Socket) -> Self {
+        Self {
+            socket,
+            sequences: [0; Topic::ALL.len()],
+        }
+    }
+
+    /// Returns the endpoint the socket was last bound to, with the port filled in when binding to
+    /// port 0 or `*`.
+    pub fn last_endpoint(&self) -> Result<String> {
+        Ok(self
+            .socket
+            .get_last_endpoint()?
+            .unwrap_or_else(|bytes| String::from_utf8_lossy(&bytes).into()))
+    }
+
+    /// Returns a reference to the ZMQ socket used by this publisher. This is useful to set socket
+    /// options or use other functions provided by [`zmq`].
+    #[inline]
+    pub fn as_zmq_socket(&self) -> &Socket {
+        &self.socket
+    }
+
+    /// Returns the sequence number the next message of `topic` is sent with.
+    #[inline]
+    pub fn next_sequence(&self, topic: Topic) -> u32 {
+        self.sequences[topic_index(topic)]
+    }
+
+    /// Sets the sequence number the next message of `topic` is sent with, for example to emulate a
+    /// node that has been running for a while.
+    #[inline]
+    pub fn set_next_sequence(&mut self, topic: Topic, sequence: u32) {
+        self.sequences[topic_index(topic)] = sequence;
+    }
+
+    /// Sends `msg` with the next sequence number of its topic instead of its own, returning the
+    /// sequence number used.
+    pub fn send(&mut self, msg: &Message) -> Result<u32> {
+        let sequence = &mut self.sequences[topic_index(msg.topic_type())];
+
+        let [topic, data, _] = msg.serialize_to_vecs();
+        self.socket
+            .send_multipart([topic, data, sequence.to_le_bytes().to_vec()], 0)?;
+
+        let used = *sequence;
+        *sequence = sequence.wrapping_add(1);
+
+        Ok(used)
+    }
+
+    /// Sends `msg` with its own sequence number, leaving the sequence numbers of this publisher
+    /// unchanged. Useful for relays that keep the sequence numbers of the node they relay.
+    #[inline]
+    pub fn forward(&self, msg: &Message) -> Result<()> {
+        Ok(self.socket.send_multipart(msg.serialize_to_vecs(), 0)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, Publisher, SubscriberBuilder, Topic};
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
+    use core::time::Duration;
+
+    #[test]
+    fn sequences() {
+        let context = zmq::Context::new();
+        let mut publisher =
+            Publisher::bind_with_context(&context, &["inproc://publisher-sequences"]).unwrap();
+
+        let rx = SubscriberBuilder::new(&["inproc://publisher-sequences"])
+            .context(context)
+            .subscribe_receiver()
+            .unwrap();
+
+        let block = Message::HashBlock(BlockHash::all_zeros(), 100);
+        let tx = Message::HashTx(Txid::all_zeros(), 100);
+
+        // the subscription may not have reached the publisher yet, so publish until received
+        let first = loop {
+            publisher.send(&block).unwrap();
+            if let Ok(msg) = rx.recv_timeout(Duration::from_millis(10)) {
+                break msg.unwrap().sequence();
+            }
+        };
+
+        assert_eq!(publisher.send(&tx).unwrap(), 0);
+        assert_eq!(publisher.send(&tx).unwrap(), 1);
+        assert_eq!(publisher.send(&block).unwrap(), first + 1);
+
+        publisher.set_next_sequence(Topic::HashTx, u32::MAX);
+        publisher.send(&tx).unwrap();
+        assert_eq!(publisher.next_sequence(Topic::HashTx), 0);
+
+        publisher.forward(&tx).unwrap();
+
+        let sequences: Vec<_> = rx
+            .iter()
+            .take(5)
+            .map(|msg| {
+                let msg = msg.unwrap();
+                (msg.topic_type(), msg.sequence())
+            })
+            .collect();
+
+        assert_eq!(
+            sequences,
+            [
+                (Topic::HashTx, 0),
+                (Topic::HashTx, 1),
+                (Topic::HashBlock, first + 1),
+                (Topic::HashTx, u32::MAX),
+                (Topic::HashTx, 100),
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    error::{Error, Result},
+    message::{Message, SEQUENCE_LEN, TOPIC_MAX_LEN},
+    topic::Topic,
+};
+use core::{cmp::min, fmt};
+
+/// A message of which only the framing has been checked: it has 3 parts, a known topic and a
+/// sequence number of [`SEQUENCE_LEN`] bytes. The data is not decoded, which saves the cost of
+/// deserializing blocks and transactions for code that only forwards or stores them. Use
+/// [`RawMessage::decode`] to get a [`Message`] later.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RawMessage {
+    pub topic: Topic,
+    pub data: Vec<u8>,
+    pub sequence: u32,
+}
+
+impl RawMessage {
+    /// Attempts to create a [`RawMessage`] from a multipart (multiple byte slices).
+    #[inline]
+    pub fn from_multipart<T: AsRef<[u8]>>(mp: &[T]) -> Result<Self> {
+        let [topic, data, seq]: &[T; 3] = mp
+            .try_into()
+            .map_err(|_| Error::InvalidMutlipartLength(mp.len()))?;
+
+        let seq = seq.as_ref();
+        let seq = seq
+            .try_into()
+            .map_err(|_| Error::InvalidSequenceLength(seq.len()))?;
+
+        Self::from_parts(topic.as_ref(), data.as_ref(), seq)
+    }
+
+    /// Creates a [`RawMessage`] from the parts of a multipart, returning an error if the topic is
+    /// unknown.
+    #[inline]
+    pub fn from_parts(topic: &[u8], data: &[u8], seq: [u8; SEQUENCE_LEN]) -> Result<Self> {
+        let Some(topic_type) = Topic::from_bytes(topic) else {
+            let mut buf = [0; TOPIC_MAX_LEN];
+
+            buf[..min(TOPIC_MAX_LEN, topic.len())]
+                .copy_from_slice(&topic[..min(TOPIC_MAX_LEN, topic.len())]);
+
+            return Err(Error::InvalidTopic(topic.len(), buf));
+        };
+
+        Ok(Self {
+            topic: topic_type,
+            data: data.to_vec(),
+            sequence: u32::from_le_bytes(seq),
+        })
+    }
+
+    /// Decodes the data into a [`Message`].
+    #[inline]
+    pub fn decode(&self) -> Result<Message> {
+        Message::from_parts(
+            self.topic.as_bytes(),
+            &self.data,
+            self.sequence.to_le_bytes(),
+        )
+    }
+
+    /// Serializes this [`RawMessage`] to 3 [`Vec<u8>`]s, the same multipart it was created from.
+    #[inline]
+    pub fn serialize_to_vecs(&self) -> [Vec<u8>; 3] {
+        [
+            self.topic.as_bytes().to_vec(),
+            self.data.clone(),
+            self.sequence.to_le_bytes().to_vec(),
+        ]
+    }
+}
+
+impl From<&Message> for RawMessage {
+    #[inline]
+    fn from(msg: &Message) -> Self {
+        Self {
+            topic: msg.topic_type(),
+            data: msg.serialize_data_to_vec(),
+            sequence: msg.sequence(),
+        }
+    }
+}
+
+impl From<Message> for RawMessage {
+    #[inline]
+    fn from(msg: Message) -> Self {
+        Self::from(&msg)
+    }
+}
+
+impl TryFrom<RawMessage> for Message {
+    type Error = Error;
+
+    #[inline]
+    fn try_from(msg: RawMessage) -> Result<Self> {
+        msg.decode()
+    }
+}
+
+impl fmt::Display for RawMessage {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RawMessage({}, {} bytes, sequence={})",
+            self.topic,
+            self.data.len(),
+            self.sequence
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Error, Message, RawMessage, Topic};
+    use bitcoin::{hashes::Hash, BlockHash};
+
+    #[test]
+    fn raw_message() {
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 5);
+
+        let raw = RawMessage::from_multipart(&msg.serialize_to_vecs()).unwrap();
+        assert_eq!(raw.topic, Topic::HashBlock);
+        assert_eq!(raw.sequence, 5);
+        assert_eq!(raw, RawMessage::from(&msg));
+        assert_eq!(raw.serialize_to_vecs(), msg.serialize_to_vecs());
+        assert_eq!(raw.decode().unwrap(), msg);
+
+        // invalid data is only noticed when decoding
+        let raw =
+            RawMessage::from_multipart(&[b"rawblock".as_slice(), &[0xff; 10], &[0; 4]]).unwrap();
+        assert!(matches!(
+            raw.decode(),
+            Err(Error::BitcoinDeserialization(_))
+        ));
+
+        assert!(matches!(
+            RawMessage::from_multipart(&[b"hashblock".as_slice(), &[0; 32]]),
+            Err(Error::InvalidMutlipartLength(2))
+        ));
+        assert!(matches!(
+            RawMessage::from_multipart(&[b"hashblock".as_slice(), &[0; 32], &[0; 3]]),
+            Err(Error::InvalidSequenceLength(3))
+        ));
+        assert!(matches!(
+            RawMessage::from_multipart(&[b"unknown".as_slice(), &[0; 32], &[0; 4]]),
+            Err(Error::InvalidTopic(7, _))
+        ));
+    }
+}
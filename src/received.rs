@@ -0,0 +1,26 @@
+use crate::message::Message;
+use core::fmt;
+
+/// A message together with the endpoint it was received from, as passed to
+/// [`SubscriberBuilder::new`][crate::SubscriberBuilder::new] or any of the subscribe functions.
+/// Useful to tell publishers apart when subscribing to multiple nodes at once.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Received<M = Message> {
+    pub endpoint: String,
+    pub message: M,
+}
+
+impl<M> Received<M> {
+    /// Returns the contained message, discarding the endpoint.
+    #[inline]
+    pub fn into_message(self) -> M {
+        self.message
+    }
+}
+
+impl<M: fmt::Display> fmt::Display for Received<M> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {}", self.message, self.endpoint)
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{
+    adapter::{Adapter, Process},
+    error::Result,
+    message::Message,
+    received::Received,
+    topic::Topic,
+};
+use core::fmt;
+use std::collections::{HashMap, VecDeque};
+
+#[cfg(feature = "async")]
+use core::{
+    pin::Pin,
+    task::{Context as AsyncContext, Poll},
+};
+#[cfg(feature = "async")]
+use futures_util::stream::Stream;
+
+/// An irregularity in the sequence numbers of a publisher, detected by a [`SequenceTracker`].
+/// Every topic on every endpoint has its own sequence number, see [`Message::sequence`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceEvent {
+    /// One or more messages were lost, `got - expected` (wrapping) messages were not received.
+    Gap {
+        endpoint: String,
+        topic: Topic,
+        expected: u32,
+        got: u32,
+    },
+    /// A message with a sequence number that was already received (or one at most
+    /// [`SequenceTracker::REORDER_WINDOW`] older) was received again.
+    Duplicate {
+        endpoint: String,
+        topic: Topic,
+        last: u32,
+        got: u32,
+    },
+    /// The sequence number went back to 0, or further back than
+    /// [`SequenceTracker::REORDER_WINDOW`] if the first message after the restart was lost. The
+    /// publisher restarted, messages published while it was down are never sent.
+    PublisherRestart {
+        endpoint: String,
+        topic: Topic,
+        last: u32,
+    },
+}
+
+impl SequenceEvent {
+    /// Returns the endpoint this [`SequenceEvent`] happened on.
+    #[inline]
+    pub fn endpoint(&self) -> &str {
+        match self {
+            Self::Gap { endpoint, .. }
+            | Self::Duplicate { endpoint, .. }
+            | Self::PublisherRestart { endpoint, .. } => endpoint,
+        }
+    }
+
+    /// Returns the topic this [`SequenceEvent`] happened on.
+    #[inline]
+    pub fn topic(&self) -> Topic {
+        match self {
+            Self::Gap { topic, .. }
+            | Self::Duplicate { topic, .. }
+            | Self::PublisherRestart { topic, .. } => *topic,
+        }
+    }
+
+    /// Returns true if messages may have been lost, meaning a consumer has to resync.
+    #[inline]
+    pub fn requires_resync(&self) -> bool {
+        !matches!(self, Self::Duplicate { .. })
+    }
+}
+
+impl fmt::Display for SequenceEvent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gap {
+                endpoint,
+                topic,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Gap({endpoint}, {topic}, expected={expected}, got={got})"
+            ),
+            Self::Duplicate {
+                endpoint,
+                topic,
+                last,
+                got,
+            } => write!(f, "Duplicate({endpoint}, {topic}, last={last}, got={got})"),
+            Self::PublisherRestart {
+                endpoint,
+                topic,
+                last,
+            } => write!(f, "PublisherRestart({endpoint}, {topic}, last={last})"),
+        }
+    }
+}
+
+/// Keeps track of the last sequence number per endpoint and topic to detect lost, duplicate and
+/// restarted messages.
+///
+/// The first message of every endpoint and topic is always accepted, after that every message
+/// should have the next sequence number. Sequence numbers wrap around after [`u32::MAX`].
+#[derive(Debug, Clone, Default)]
+pub struct SequenceTracker {
+    last: HashMap<String, [Option<u32>; Topic::ALL.len()]>,
+}
+
+impl SequenceTracker {
+    /// How far a sequence number may go back to be reported as a
+    /// [`Duplicate`][SequenceEvent::Duplicate]. Going back further means the publisher restarted
+    /// and its first messages were lost.
+    pub const REORDER_WINDOW: u32 = 16;
+
+    /// Creates a new [`SequenceTracker`] that has not seen any messages yet.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the last accepted sequence number of `topic` on `endpoint`.
+    #[inline]
+    pub fn last_sequence(&self, endpoint: &str, topic: Topic) -> Option<u32> {
+        self.last.get(endpoint)?[topic as usize]
+    }
+
+    /// Forgets all sequence numbers of `endpoint`, the next message from it is accepted as the
+    /// first one.
+    #[inline]
+    pub fn forget(&mut self, endpoint: &str) {
+        self.last.remove(endpoint);
+    }
+
+    /// Checks the sequence number of a message received from `endpoint`, returning a
+    /// [`SequenceEvent`] if it is not the next expected one.
+    pub fn track(&mut self, endpoint: &str, message: &Message) -> Option<SequenceEvent> {
+        let topic = message.topic_type();
+        let got = message.sequence();
+
+        let last = match self.last.get_mut(endpoint) {
+            Some(last) => &mut last[topic as usize],
+            None => {
+                let last = self.last.entry(endpoint.to_owned()).or_default();
+                &mut last[topic as usize]
+            }
+        };
+
+        let Some(prev) = *last else {
+            *last = Some(got);
+            return None;
+        };
+
+        let expected = prev.wrapping_add(1);
+
+        let event = if got == expected {
+            None
+        } else if got == 0
+            || ((got.wrapping_sub(prev) as i32) <= 0
+                && prev.wrapping_sub(got) > Self::REORDER_WINDOW)
+        {
+            Some(SequenceEvent::PublisherRestart {
+                endpoint: endpoint.to_owned(),
+                topic,
+                last: prev,
+            })
+        } else if (got.wrapping_sub(prev) as i32) > 0 {
+            Some(SequenceEvent::Gap {
+                endpoint: endpoint.to_owned(),
+                topic,
+                expected,
+                got,
+            })
+        } else {
+            // do not go back to an older sequence number
+            return Some(SequenceEvent::Duplicate {
+                endpoint: endpoint.to_owned(),
+                topic,
+                last: prev,
+                got,
+            });
+        };
+
+        *last = Some(got);
+
+        event
+    }
+
+    /// Same as [`SequenceTracker::track`], using the endpoint of the [`Received`] message.
+    #[inline]
+    pub fn track_received(&mut self, received: &Received) -> Option<SequenceEvent> {
+        self.track(&received.endpoint, &received.message)
+    }
+}
+
+/// A [`Received`] message or a [`SequenceEvent`], produced by [`SequenceTracked`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackedMessage {
+    Message(Received),
+    Event(SequenceEvent),
+}
+
+/// Wraps an [`Iterator`] (like a [`Receiver`]) or a `Stream` of [`Received`] messages and checks
+/// their sequence numbers with a [`SequenceTracker`]. A [`SequenceEvent`] is yielded right before
+/// the message that caused it.
+///
+/// ```no_run
+/// use ghostcore_zmq::{subscribe_receiver_with_source, SequenceTracked, TrackedMessage};
+///
+/// let rx = subscribe_receiver_with_source(&["tcp://127.0.0.1:28332"]).unwrap();
+///
+/// for msg in SequenceTracked::new(rx.into_iter()) {
+///     match msg {
+///         Ok(TrackedMessage::Message(received)) => println!("Received message: {received}"),
+///         Ok(TrackedMessage::Event(event)) => println!("Sequence event: {event}"),
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+/// }
+/// ```
+///
+/// [`Receiver`]: std::sync::mpsc::Receiver
+#[derive(Debug)]
+pub struct SequenceTracked<I> {
+    adapter: Adapter<I, SequenceTracker>,
+}
+
+impl<I> SequenceTracked<I> {
+    /// Wraps `inner` using a new [`SequenceTracker`].
+    #[inline]
+    pub fn new(inner: I) -> Self {
+        Self::with_tracker(inner, SequenceTracker::new())
+    }
+
+    /// Wraps `inner` using an existing [`SequenceTracker`].
+    #[inline]
+    pub fn with_tracker(inner: I, tracker: SequenceTracker) -> Self {
+        Self {
+            adapter: Adapter::new(inner, tracker),
+        }
+    }
+
+    /// Returns a reference to the [`SequenceTracker`] used.
+    #[inline]
+    pub fn tracker(&self) -> &SequenceTracker {
+        &self.adapter.processor
+    }
+
+    /// Returns a mutable reference to the [`SequenceTracker`] used, for example to
+    /// [`forget`][SequenceTracker::forget] an endpoint after resyncing.
+    #[inline]
+    pub fn tracker_mut(&mut self) -> &mut SequenceTracker {
+        &mut self.adapter.processor
+    }
+
+    /// Returns the wrapped iterator or stream.
+    #[inline]
+    pub fn into_inner(self) -> I {
+        self.adapter.inner
+    }
+}
+
+impl Process for SequenceTracker {
+    type Input = Received;
+    type Output = TrackedMessage;
+
+    fn process(&mut self, received: Received, out: &mut VecDeque<TrackedMessage>) {
+        if let Some(event) = self.track_received(&received) {
+            out.push_back(TrackedMessage::Event(event));
+        }
+        out.push_back(TrackedMessage::Message(received));
+    }
+}
+
+impl<I: Iterator<Item = Result<Received>>> Iterator for SequenceTracked<I> {
+    type Item = Result<TrackedMessage>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.adapter.next()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<S: Stream<Item = Result<Received>> + Unpin> Stream for SequenceTracked<S> {
+    type Item = Result<TrackedMessage>;
+
+    #[inline]
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut AsyncContext<'_>) -> Poll<Option<Self::Item>> {
+        self.adapter.poll_next(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        adapter::tests::{hashblock, received},
+        Message, SequenceEvent, SequenceTracked, SequenceTracker, Topic, TrackedMessage,
+    };
+    use bitcoin::{hashes::Hash, Txid};
+
+    #[test]
+    fn tracker() {
+        let mut tracker = SequenceTracker::new();
+
+        assert_eq!(tracker.track("a", &hashblock(5)), None);
+        assert_eq!(tracker.track("a", &hashblock(6)), None);
+        // other endpoints and topics have their own sequence
+        assert_eq!(tracker.track("b", &hashblock(100)), None);
+        assert_eq!(
+            tracker.track("a", &Message::HashTx(Txid::all_zeros(), 0)),
+            None
+        );
+
+        assert_eq!(
+            tracker.track("a", &hashblock(9)),
+            Some(SequenceEvent::Gap {
+                endpoint: "a".to_owned(),
+                topic: Topic::HashBlock,
+                expected: 7,
+                got: 9
+            })
+        );
+        assert_eq!(
+            tracker.track("a", &hashblock(9)),
+            Some(SequenceEvent::Duplicate {
+                endpoint: "a".to_owned(),
+                topic: Topic::HashBlock,
+                last: 9,
+                got: 9
+            })
+        );
+        assert_eq!(
+            tracker.track("a", &hashblock(3)),
+            Some(SequenceEvent::Duplicate {
+                endpoint: "a".to_owned(),
+                topic: Topic::HashBlock,
+                last: 9,
+                got: 3
+            })
+        );
+        assert_eq!(tracker.last_sequence("a", Topic::HashBlock), Some(9));
+        assert_eq!(tracker.track("a", &hashblock(10)), None);
+
+        assert_eq!(
+            tracker.track("a", &hashblock(0)),
+            Some(SequenceEvent::PublisherRestart {
+                endpoint: "a".to_owned(),
+                topic: Topic::HashBlock,
+                last: 10
+            })
+        );
+        assert_eq!(tracker.track("a", &hashblock(1)), None);
+
+        tracker.forget("b");
+        assert_eq!(tracker.last_sequence("b", Topic::HashBlock), None);
+
+        // wrapping around is not a restart
+        assert_eq!(tracker.track("b", &hashblock(u32::MAX)), None);
+        assert_eq!(tracker.track("b", &hashblock(0)), None);
+    }
+
+    #[test]
+    fn restart_first_message_lost() {
+        let mut tracker = SequenceTracker::new();
+
+        assert_eq!(tracker.track("a", &hashblock(1000)), None);
+        // sequence 0 after the restart was lost
+        assert_eq!(
+            tracker.track("a", &hashblock(1)),
+            Some(SequenceEvent::PublisherRestart {
+                endpoint: "a".to_owned(),
+                topic: Topic::HashBlock,
+                last: 1000
+            })
+        );
+        assert_eq!(tracker.last_sequence("a", Topic::HashBlock), Some(1));
+        assert_eq!(tracker.track("a", &hashblock(2)), None);
+
+        // a jump back within the reorder window is still a duplicate
+        assert!(matches!(
+            tracker.track("a", &hashblock(1)),
+            Some(SequenceEvent::Duplicate { .. })
+        ));
+    }
+
+    #[test]
+    fn interleave() {
+        let items: Vec<_> = SequenceTracked::new(
+            [0, 1, 3]
+                .into_iter()
+                .map(|seq| Ok(received("a", hashblock(seq)))),
+        )
+        .map(Result::unwrap)
+        .collect();
+
+        assert_eq!(
+            items,
+            [
+                TrackedMessage::Message(received("a", hashblock(0))),
+                TrackedMessage::Message(received("a", hashblock(1))),
+                TrackedMessage::Event(SequenceEvent::Gap {
+                    endpoint: "a".to_owned(),
+                    topic: Topic::HashBlock,
+                    expected: 2,
+                    got: 3
+                }),
+                TrackedMessage::Message(received("a", hashblock(3))),
+            ]
+        );
+    }
+}
@@ -1,6 +1,98 @@
-use super::{new_socket_internal, subscribe_internal};
-use crate::{error::Result, message::Message};
-use core::{convert::Infallible, ops::ControlFlow};
+use super::builder::SubscriberBuilder;
+use crate::{
+    error::{Error, Result},
+    message::Message,
+    monitor::SocketMessage,
+    raw_message::RawMessage,
+    received::Received,
+    topic::Topic,
+};
+use core::{convert::Infallible, fmt, ops::ControlFlow, time::Duration};
+
+/// A [`Message`] (or a [`RawMessage`]) or a notice that no message was received for some time,
+/// passed to the callback of [`subscribe_blocking_timeout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubscribeEvent<M = Message> {
+    Message(M),
+    /// No message was received within the timeout. Useful to do periodic work, like emitting
+    /// heartbeats or checking if the subscriber should stop.
+    Idle,
+}
+
+impl<M: fmt::Display> fmt::Display for SubscribeEvent<M> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(msg) => msg.fmt(f),
+            Self::Idle => f.write_str("Idle"),
+        }
+    }
+}
+
+/// Handles the messages of a blocking subscription, as an alternative to a callback. Every method
+/// returns [`ControlFlow::Break`] to stop the subscription. Implement `Handler<B, RawMessage>` to
+/// use it with [`subscribe_raw_blocking_handler`].
+///
+/// ```no_run
+/// use core::ops::ControlFlow;
+/// use ghostcore_zmq::{subscribe_blocking_handler, Error, Handler, Message};
+///
+/// struct Counter {
+///     messages: u64,
+/// }
+///
+/// impl Handler for Counter {
+///     fn on_message(&mut self, message: Message) -> ControlFlow<()> {
+///         self.messages += 1;
+///         println!("Received message #{}: {message}", self.messages);
+///         ControlFlow::Continue(())
+///     }
+///
+///     fn on_error(&mut self, error: Error) -> ControlFlow<()> {
+///         println!("Error receiving message: {error}");
+///         ControlFlow::Break(())
+///     }
+/// }
+///
+/// let mut counter = Counter { messages: 0 };
+/// subscribe_blocking_handler(&["tcp://127.0.0.1:28332"], &mut counter).unwrap();
+/// println!("Received {} messages", counter.messages);
+/// ```
+pub trait Handler<B = (), M = Message> {
+    /// Called for every received message.
+    fn on_message(&mut self, message: M) -> ControlFlow<B>;
+
+    /// Called when receiving a message failed. By default, errors are ignored.
+    #[inline]
+    fn on_error(&mut self, error: Error) -> ControlFlow<B> {
+        let _ = error;
+        ControlFlow::Continue(())
+    }
+
+    /// Called when no message was received within the receive timeout (see
+    /// [`SubscriberBuilder::receive_timeout`]). Never called if no receive timeout is set.
+    #[inline]
+    fn on_idle(&mut self) -> ControlFlow<B> {
+        ControlFlow::Continue(())
+    }
+}
+
+impl<H: Handler<B, M> + ?Sized, B, M> Handler<B, M> for &mut H {
+    #[inline]
+    fn on_message(&mut self, message: M) -> ControlFlow<B> {
+        (**self).on_message(message)
+    }
+
+    #[inline]
+    fn on_error(&mut self, error: Error) -> ControlFlow<B> {
+        (**self).on_error(error)
+    }
+
+    #[inline]
+    fn on_idle(&mut self) -> ControlFlow<B> {
+        (**self).on_idle()
+    }
+}
 
 /// Subscribes to a single ZMQ endpoint and blocks the thread until [`ControlFlow::Break`] is
 /// returned by the callback.
@@ -14,7 +106,7 @@ pub fn subscribe_single_blocking<F, B>(
     callback: F,
 ) -> Result<ControlFlow<B, Infallible>>
 where
-    F: Fn(Result<Message>) -> ControlFlow<B>,
+    F: FnMut(Result<Message>) -> ControlFlow<B>,
 {
     subscribe_blocking(&[endpoint], callback)
 }
@@ -31,7 +123,7 @@ pub fn subscribe_multi_blocking<F, B>(
     callback: F,
 ) -> Result<ControlFlow<B, Infallible>>
 where
-    F: Fn(Result<Message>) -> ControlFlow<B>,
+    F: FnMut(Result<Message>) -> ControlFlow<B>,
 {
     subscribe_blocking(endpoints, callback)
 }
@@ -44,9 +136,194 @@ pub fn subscribe_blocking<F, B>(
     callback: F,
 ) -> Result<ControlFlow<B, Infallible>>
 where
-    F: Fn(Result<Message>) -> ControlFlow<B>,
+    F: FnMut(Result<Message>) -> ControlFlow<B>,
 {
-    let (_context, socket) = new_socket_internal(endpoints)?;
+    SubscriberBuilder::new(endpoints).subscribe_blocking(callback)
+}
+
+/// Subscribes to multiple ZMQ endpoints and blocks the thread until [`ControlFlow::Break`] is
+/// returned by the callback. The callback receives [`RawMessage`]s, which are not decoded.
+///
+/// ```no_run
+/// use core::ops::ControlFlow;
+/// use ghostcore_zmq::subscribe_raw_blocking;
+///
+/// subscribe_raw_blocking(&["tcp://127.0.0.1:28332"], |msg| {
+///     match msg {
+///         Ok(msg) => println!("Received {} bytes on {}", msg.data.len(), msg.topic),
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+///
+///     ControlFlow::<()>::Continue(())
+/// })
+/// .unwrap();
+/// ```
+#[inline]
+pub fn subscribe_raw_blocking<F, B>(
+    endpoints: &[&str],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<RawMessage>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_raw_blocking(callback)
+}
+
+/// Subscribes to multiple ZMQ endpoints, only receiving messages of the given topics, and blocks
+/// the thread until [`ControlFlow::Break`] is returned by the callback. See
+/// [`SubscriberBuilder::topics`].
+///
+/// # Panics
+///
+/// Panics if `topics` is empty.
+#[inline]
+pub fn subscribe_blocking_topics<F, B>(
+    endpoints: &[&str],
+    topics: &[Topic],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<Message>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints)
+        .topics(topics)
+        .subscribe_blocking(callback)
+}
+
+/// Subscribes to multiple ZMQ endpoints and blocks the thread until [`ControlFlow::Break`] is
+/// returned by the callback. Every message is tagged with the endpoint it was received from.
+#[inline]
+pub fn subscribe_blocking_with_source<F, B>(
+    endpoints: &[&str],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<Received>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_blocking_with_source(callback)
+}
+
+/// Like [`subscribe_blocking_with_source`], but the callback receives [`RawMessage`]s, which are
+/// not decoded.
+#[inline]
+pub fn subscribe_raw_blocking_with_source<F, B>(
+    endpoints: &[&str],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<Received<RawMessage>>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_raw_blocking_with_source(callback)
+}
 
-    Ok(subscribe_internal(socket, callback))
+/// Subscribes to multiple ZMQ endpoints and blocks the thread until [`ControlFlow::Break`] is
+/// returned by the callback. The callback is called with [`SubscribeEvent::Idle`] whenever no
+/// message was received for `timeout`, so it gets a chance to stop even if the publisher is quiet.
+///
+/// ```no_run
+/// use core::{
+///     ops::ControlFlow,
+///     sync::atomic::{AtomicBool, Ordering},
+///     time::Duration,
+/// };
+/// use ghostcore_zmq::{subscribe_blocking_timeout, SubscribeEvent};
+///
+/// static STOP: AtomicBool = AtomicBool::new(false);
+///
+/// subscribe_blocking_timeout(
+///     &["tcp://127.0.0.1:28332"],
+///     Duration::from_secs(1),
+///     |event| {
+///         match event {
+///             Ok(SubscribeEvent::Message(msg)) => println!("Received message: {msg}"),
+///             Ok(SubscribeEvent::Idle) => println!("No messages received in the last second"),
+///             Err(err) => println!("Error receiving message: {err}"),
+///         }
+///
+///         if STOP.load(Ordering::Relaxed) {
+///             ControlFlow::Break(())
+///         } else {
+///             ControlFlow::Continue(())
+///         }
+///     },
+/// )
+/// .unwrap();
+/// ```
+#[inline]
+pub fn subscribe_blocking_timeout<F, B>(
+    endpoints: &[&str],
+    timeout: Duration,
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<SubscribeEvent>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_blocking_timeout(timeout, callback)
+}
+
+/// Like [`subscribe_blocking_timeout`], but the callback receives [`RawMessage`]s, which are not
+/// decoded.
+#[inline]
+pub fn subscribe_raw_blocking_timeout<F, B>(
+    endpoints: &[&str],
+    timeout: Duration,
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<SubscribeEvent<RawMessage>>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_raw_blocking_timeout(timeout, callback)
+}
+
+/// Subscribes to multiple ZMQ endpoints and passes every message to `handler`, blocking the thread
+/// until one of its methods returns [`ControlFlow::Break`].
+#[inline]
+pub fn subscribe_blocking_handler<H, B>(
+    endpoints: &[&str],
+    handler: H,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    H: Handler<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_blocking_handler(handler)
+}
+
+/// Like [`subscribe_blocking_handler`], but `handler` receives [`RawMessage`]s, which are not
+/// decoded.
+#[inline]
+pub fn subscribe_raw_blocking_handler<H, B>(
+    endpoints: &[&str],
+    handler: H,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    H: Handler<B, RawMessage>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_raw_blocking_handler(handler)
+}
+
+/// Subscribes to multiple ZMQ endpoints and blocks the thread until [`ControlFlow::Break`] is
+/// returned by the callback. Besides messages, the callback receives events (see
+/// [`MonitorMessage`][crate::MonitorMessage]).
+#[inline]
+pub fn subscribe_blocking_monitor<F, B>(
+    endpoints: &[&str],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<SocketMessage>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_blocking_monitor(callback)
+}
+
+/// Like [`subscribe_blocking_monitor`], but the callback receives [`RawMessage`]s, which are not
+/// decoded.
+#[inline]
+pub fn subscribe_raw_blocking_monitor<F, B>(
+    endpoints: &[&str],
+    callback: F,
+) -> Result<ControlFlow<B, Infallible>>
+where
+    F: FnMut(Result<SocketMessage<RawMessage>>) -> ControlFlow<B>,
+{
+    SubscriberBuilder::new(endpoints).subscribe_raw_blocking_monitor(callback)
 }
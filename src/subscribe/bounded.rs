@@ -0,0 +1,406 @@
+use super::FromParts;
+use crate::{error::Result, message::Message, topic::Topic};
+use core::time::Duration;
+use std::{
+    collections::VecDeque,
+    sync::{
+        mpsc::{RecvError, RecvTimeoutError, TryRecvError},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    time::Instant,
+};
+
+/// What to do with a message when the queue of a [`BoundedReceiver`] is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Block the background thread until there is space. Messages then queue up in ZMQ, which
+    /// drops them when the receive high water mark (see
+    /// [`SubscriberBuilder::receive_high_water_mark`]) is reached. These drops are not counted.
+    ///
+    /// [`SubscriberBuilder::receive_high_water_mark`]: crate::SubscriberBuilder::receive_high_water_mark
+    Block,
+    /// Drop the new message.
+    DropNewest,
+    /// Drop the oldest message in the queue to make space for the new message.
+    DropOldest,
+    /// Drop transaction messages (`hashtx`, `hashwtx` and `rawtx`) only: a new transaction
+    /// message is dropped, any other message replaces the oldest transaction message in the queue.
+    /// If the queue only contains other messages, the background thread blocks like
+    /// [`OverflowPolicy::Block`].
+    DropTransactions,
+}
+
+fn is_transaction<M: FromParts>(msg: &Result<M>) -> bool {
+    matches!(
+        msg.as_ref().map(M::topic_type),
+        Ok(Topic::HashTx | Topic::HashWTx | Topic::RawTx)
+    )
+}
+
+#[derive(Debug)]
+struct State<M> {
+    queue: VecDeque<Result<M>>,
+    dropped: u64,
+    sender_alive: bool,
+    receiver_alive: bool,
+}
+
+#[derive(Debug)]
+struct Shared<M> {
+    state: Mutex<State<M>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+impl<M> Shared<M> {
+    fn lock(&self) -> MutexGuard<'_, State<M>> {
+        // a panic while holding the lock can not leave the state inconsistent
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Sending half of a bounded channel, used on the background thread.
+#[derive(Debug)]
+pub(super) struct BoundedSender<M> {
+    shared: Arc<Shared<M>>,
+}
+
+impl<M: FromParts> BoundedSender<M> {
+    /// Sends a message, applying the overflow policy. Returns `Err(())` if the
+    /// [`BoundedReceiver`] was dropped.
+    pub(super) fn send(&self, msg: Result<M>) -> core::result::Result<(), ()> {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+
+        loop {
+            if !state.receiver_alive {
+                return Err(());
+            }
+
+            if state.queue.len() < shared.capacity {
+                break;
+            }
+
+            match shared.policy {
+                OverflowPolicy::Block => {}
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(());
+                }
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.dropped += 1;
+                    break;
+                }
+                OverflowPolicy::DropTransactions => {
+                    if is_transaction(&msg) {
+                        state.dropped += 1;
+                        return Ok(());
+                    }
+
+                    if let Some(index) = state.queue.iter().position(is_transaction) {
+                        state.queue.remove(index);
+                        state.dropped += 1;
+                        break;
+                    }
+                }
+            }
+
+            state = shared
+                .not_full
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+
+        state.queue.push_back(msg);
+        shared.not_empty.notify_one();
+
+        Ok(())
+    }
+}
+
+impl<M> Drop for BoundedSender<M> {
+    fn drop(&mut self) {
+        self.shared.lock().sender_alive = false;
+        self.shared.not_empty.notify_all();
+    }
+}
+
+/// Like a [`Receiver`][std::sync::mpsc::Receiver], but holds at most a fixed number of messages.
+/// What happens when it is full is determined by its [`OverflowPolicy`]. Returned by
+/// [`subscribe_receiver_bounded`][crate::subscribe_receiver_bounded].
+#[derive(Debug)]
+pub struct BoundedReceiver<M = Message> {
+    shared: Arc<Shared<M>>,
+}
+
+/// Creates a bounded channel.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+pub(super) fn bounded_channel<M>(
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (BoundedSender<M>, BoundedReceiver<M>) {
+    assert!(capacity > 0, "capacity must be at least 1");
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(capacity),
+            dropped: 0,
+            sender_alive: true,
+            receiver_alive: true,
+        }),
+        capacity,
+        policy,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+
+    (
+        BoundedSender {
+            shared: shared.clone(),
+        },
+        BoundedReceiver { shared },
+    )
+}
+
+impl<M> BoundedReceiver<M> {
+    /// Blocks until a message is available, see [`Receiver::recv`].
+    ///
+    /// [`Receiver::recv`]: std::sync::mpsc::Receiver::recv
+    pub fn recv(&self) -> core::result::Result<Result<M>, RecvError> {
+        let shared = &*self.shared;
+        let mut state = shared.lock();
+
+        loop {
+            if let Some(msg) = state.queue.pop_front() {
+                shared.not_full.notify_one();
+                return Ok(msg);
+            }
+
+            if !state.sender_alive {
+                return Err(RecvError);
+            }
+
+            state = shared
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|err| err.into_inner());
+        }
+    }
+
+    /// Returns a message if one is available without blocking, see [`Receiver::try_recv`].
+    ///
+    /// [`Receiver::try_recv`]: std::sync::mpsc::Receiver::try_recv
+    pub fn try_recv(&self) -> core::result::Result<Result<M>, TryRecvError> {
+        let mut state = self.shared.lock();
+
+        match state.queue.pop_front() {
+            Some(msg) => {
+                self.shared.not_full.notify_one();
+                Ok(msg)
+            }
+            None if state.sender_alive => Err(TryRecvError::Empty),
+            None => Err(TryRecvError::Disconnected),
+        }
+    }
+
+    /// Blocks until a message is available or `timeout` expires, see [`Receiver::recv_timeout`].
+    ///
+    /// [`Receiver::recv_timeout`]: std::sync::mpsc::Receiver::recv_timeout
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> core::result::Result<Result<M>, RecvTimeoutError> {
+        let shared = &*self.shared;
+        let deadline = Instant::now() + timeout;
+        let mut state = shared.lock();
+
+        loop {
+            if let Some(msg) = state.queue.pop_front() {
+                shared.not_full.notify_one();
+                return Ok(msg);
+            }
+
+            if !state.sender_alive {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+
+            state = shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|err| err.into_inner())
+                .0;
+        }
+    }
+
+    /// Returns an iterator that blocks waiting for messages, see [`Receiver::iter`].
+    ///
+    /// [`Receiver::iter`]: std::sync::mpsc::Receiver::iter
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Result<M>> + '_ {
+        core::iter::from_fn(|| self.recv().ok())
+    }
+
+    /// Returns the number of messages dropped because the queue was full.
+    #[inline]
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Returns the number of messages in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Returns true if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.shared.lock().queue.is_empty()
+    }
+
+    /// Returns the maximum number of messages in the queue.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// Returns the [`OverflowPolicy`] of this receiver.
+    #[inline]
+    pub fn policy(&self) -> OverflowPolicy {
+        self.shared.policy
+    }
+}
+
+impl<M> Drop for BoundedReceiver<M> {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_alive = false;
+        self.shared.not_full.notify_all();
+    }
+}
+
+/// An iterator over the messages of a [`BoundedReceiver`], created by its [`IntoIterator`]
+/// implementation.
+#[derive(Debug)]
+pub struct BoundedIntoIter<M = Message> {
+    rx: BoundedReceiver<M>,
+}
+
+impl<M> Iterator for BoundedIntoIter<M> {
+    type Item = Result<M>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<M> IntoIterator for BoundedReceiver<M> {
+    type Item = Result<M>;
+    type IntoIter = BoundedIntoIter<M>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        BoundedIntoIter { rx: self }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::bounded_channel;
+    use crate::{transaction::tests::sample_transaction, Message, OverflowPolicy, RawMessage};
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
+    use core::time::Duration;
+    use std::{sync::mpsc::TryRecvError, thread};
+
+    fn block(n: u32) -> Message {
+        Message::HashBlock(BlockHash::all_zeros(), n)
+    }
+
+    fn tx(n: u32) -> Message {
+        Message::HashTx(Txid::all_zeros(), n)
+    }
+
+    fn drain(rx: &super::BoundedReceiver) -> Vec<Message> {
+        core::iter::from_fn(|| rx.try_recv().ok().map(Result::unwrap)).collect()
+    }
+
+    #[test]
+    fn drop_policies() {
+        let (sender, rx) = bounded_channel(2, OverflowPolicy::DropNewest);
+        for n in 0..4 {
+            sender.send(Ok(block(n))).unwrap();
+        }
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(drain(&rx), [block(0), block(1)]);
+
+        let (sender, rx) = bounded_channel(2, OverflowPolicy::DropOldest);
+        for n in 0..4 {
+            sender.send(Ok(block(n))).unwrap();
+        }
+        assert_eq!(rx.dropped(), 2);
+        assert_eq!(drain(&rx), [block(2), block(3)]);
+
+        let (sender, rx) = bounded_channel(3, OverflowPolicy::DropTransactions);
+        sender.send(Ok(tx(0))).unwrap();
+        sender.send(Ok(block(0))).unwrap();
+        sender
+            .send(Ok(Message::Tx(sample_transaction(), 1)))
+            .unwrap();
+        // full, the new transaction is dropped
+        sender.send(Ok(tx(2))).unwrap();
+        // full, the oldest transaction makes space
+        sender.send(Ok(block(1))).unwrap();
+        sender.send(Ok(block(2))).unwrap();
+        assert_eq!(rx.dropped(), 3);
+        assert_eq!(drain(&rx), [block(0), block(1), block(2)]);
+
+        drop(rx);
+        assert!(sender.send(Ok(block(3))).is_err());
+    }
+
+    #[test]
+    fn drop_transactions_raw() {
+        let (sender, rx) = bounded_channel(2, OverflowPolicy::DropTransactions);
+        sender.send(Ok(RawMessage::from(tx(0)))).unwrap();
+        sender.send(Ok(RawMessage::from(block(0)))).unwrap();
+        sender.send(Ok(RawMessage::from(block(1)))).unwrap();
+        assert_eq!(rx.dropped(), 1);
+        assert_eq!(
+            core::iter::from_fn(|| rx.try_recv().ok().map(Result::unwrap)).collect::<Vec<_>>(),
+            [RawMessage::from(block(0)), RawMessage::from(block(1))]
+        );
+    }
+
+    #[test]
+    fn block_policy() {
+        let (sender, rx) = bounded_channel(1, OverflowPolicy::Block);
+
+        let handle = thread::spawn(move || {
+            for n in 0..3 {
+                sender.send(Ok(block(n))).unwrap();
+            }
+        });
+
+        for n in 0..3 {
+            assert_eq!(
+                rx.recv_timeout(Duration::from_secs(10)).unwrap().unwrap(),
+                block(n)
+            );
+        }
+
+        handle.join().unwrap();
+        assert_eq!(rx.dropped(), 0);
+        assert_eq!(rx.try_recv().unwrap_err(), TryRecvError::Disconnected);
+    }
+}
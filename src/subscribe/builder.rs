@@ -0,0 +1,843 @@
+use super::{
+    blocking::{Handler, SubscribeEvent},
+    bounded::{BoundedReceiver, OverflowPolicy},
+    handle::SubscriptionHandle,
+    receiver::{
+        bounded_receiver_internal, receiver_internal, receiver_monitor_internal,
+        receiver_with_handle_internal, receiver_with_source_internal,
+    },
+    subscribe_internal, subscribe_monitor_internal, subscribe_timeout_internal,
+    subscribe_with_source_internal, unique_inproc_endpoint, wait_handshake_internal, FromParts,
+    Tap,
+};
+use crate::{
+    capture::{recorder::Recorder, tap::CaptureTap, CaptureWriter},
+    error::Result,
+    message::Message,
+    monitor::{
+        handshake::{HandshakeReport, Timeout},
+        SocketMessage,
+    },
+    raw_message::RawMessage,
+    received::Received,
+    topic::Topic,
+    watchdog::{Watchdog, Watched},
+};
+use core::{convert::Infallible, ops::ControlFlow, time::Duration};
+use std::{io::Write, sync::mpsc::Receiver};
+use zmq::{Context, Socket};
+
+#[cfg(feature = "async")]
+use super::stream::{
+    subscribe_async_monitor_stream, subscribe_async_stream::MessageStream, RawMessageStream,
+    SourceMessageStream,
+};
+
+/// Builder to configure the ZMQ socket(s) used to subscribe before subscribing with any of the
+/// subscription styles: a blocking callback loop ([`subscribe_blocking`]), a [`Receiver`]
+/// ([`subscribe_receiver`]) or an asynchronous stream (`subscribe_async`).
+///
+/// Options that are not set are left at ZMQ's defaults. See the `zmq_setsockopt` manual page
+/// (`man zmq_setsockopt`) for a detailed description of each option.
+///
+/// ```no_run
+/// use core::time::Duration;
+/// use ghostcore_zmq::{SubscriberBuilder, Topic};
+///
+/// let rx = SubscriberBuilder::new(&["tcp://127.0.0.1:28332"])
+///     .topics(&[Topic::HashBlock, Topic::Sequence])
+///     .receive_high_water_mark(10_000)
+///     .reconnect_interval(Duration::from_millis(500))
+///     .heartbeat_interval(Duration::from_secs(10))
+///     .subscribe_receiver()
+///     .unwrap();
+/// ```
+///
+/// [`subscribe_blocking`]: SubscriberBuilder::subscribe_blocking
+/// [`subscribe_receiver`]: SubscriberBuilder::subscribe_receiver
+#[derive(Clone)]
+pub struct SubscriberBuilder {
+    endpoints: Vec<String>,
+    topics: Option<Vec<Topic>>,
+    context: Option<Context>,
+    capture: Option<CaptureTap>,
+    rcvhwm: Option<i32>,
+    rcvtimeo: Option<i32>,
+    tcp_keepalive: Option<bool>,
+    tcp_keepalive_idle: Option<i32>,
+    tcp_keepalive_cnt: Option<i32>,
+    tcp_keepalive_intvl: Option<i32>,
+    reconnect_ivl: Option<i32>,
+    reconnect_ivl_max: Option<i32>,
+    heartbeat_ivl: Option<i32>,
+    heartbeat_timeout: Option<i32>,
+    heartbeat_ttl: Option<i32>,
+}
+
+/// Converts a [`Duration`] to milliseconds for use as a socket option, saturating at
+/// [`i32::MAX`].
+fn duration_to_ms(duration: Duration) -> i32 {
+    duration.as_millis().try_into().unwrap_or(i32::MAX)
+}
+
+/// Converts a [`Duration`] to seconds for use as a socket option, saturating at [`i32::MAX`].
+fn duration_to_secs(duration: Duration) -> i32 {
+    duration.as_secs().try_into().unwrap_or(i32::MAX)
+}
+
+impl SubscriberBuilder {
+    /// Creates a new [`SubscriberBuilder`] that will subscribe to all topics on `endpoints`, with
+    /// all socket options at their defaults.
+    pub fn new(endpoints: &[&str]) -> Self {
+        Self {
+            endpoints: endpoints.iter().map(|&e| e.to_owned()).collect(),
+            topics: None,
+            context: None,
+            capture: None,
+            rcvhwm: None,
+            rcvtimeo: None,
+            tcp_keepalive: None,
+            tcp_keepalive_idle: None,
+            tcp_keepalive_cnt: None,
+            tcp_keepalive_intvl: None,
+            reconnect_ivl: None,
+            reconnect_ivl_max: None,
+            heartbeat_ivl: None,
+            heartbeat_timeout: None,
+            heartbeat_ttl: None,
+        }
+    }
+
+    /// Only receive messages of the given topics. Filtering happens on the publisher's side, so
+    /// unwanted messages are never sent over the network. By default, all topics are received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `topics` is empty, as nothing would ever be received.
+    pub fn topics(mut self, topics: &[Topic]) -> Self {
+        assert!(!topics.is_empty(), "at least one topic is required");
+
+        self.topics = Some(topics.to_vec());
+        self
+    }
+
+    /// Creates the socket(s) in an existing ZMQ [`Context`] instead of a new one. This allows
+    /// sharing ZMQ's I/O threads between subscribers and is required to subscribe to `inproc://`
+    /// endpoints of a publisher in the same process.
+    pub fn context(mut self, context: Context) -> Self {
+        self.context = Some(context);
+        self
+    }
+
+    /// Records every message received by the subscription to `tap` before it is decoded, see
+    /// [`CaptureTap`].
+    pub fn capture(mut self, tap: CaptureTap) -> Self {
+        self.capture = Some(tap);
+        self
+    }
+
+    /// Sets the maximum number of messages queued in memory before ZMQ starts dropping them
+    /// (`ZMQ_RCVHWM`).
+    pub fn receive_high_water_mark(mut self, hwm: i32) -> Self {
+        self.rcvhwm = Some(hwm);
+        self
+    }
+
+    /// Sets the timeout of receiving a message (`ZMQ_RCVTIMEO`). When it expires, a
+    /// [`Error::Zmq`] with [`zmq::Error::EAGAIN`] is produced instead of a message. This has no
+    /// effect on asynchronous streams.
+    ///
+    /// [`Error::Zmq`]: crate::Error::Zmq
+    pub fn receive_timeout(mut self, timeout: Duration) -> Self {
+        self.rcvtimeo = Some(duration_to_ms(timeout));
+        self
+    }
+
+    /// Enables or disables TCP keepalive (`ZMQ_TCP_KEEPALIVE`). By default, the operating
+    /// system's setting is used.
+    pub fn tcp_keepalive(mut self, enable: bool) -> Self {
+        self.tcp_keepalive = Some(enable);
+        self
+    }
+
+    /// Sets the time a connection has to be idle before keepalive probes are sent
+    /// (`ZMQ_TCP_KEEPALIVE_IDLE`), with a resolution of seconds.
+    pub fn tcp_keepalive_idle(mut self, idle: Duration) -> Self {
+        self.tcp_keepalive_idle = Some(duration_to_secs(idle));
+        self
+    }
+
+    /// Sets the number of unanswered keepalive probes before the connection is considered dead
+    /// (`ZMQ_TCP_KEEPALIVE_CNT`).
+    pub fn tcp_keepalive_count(mut self, count: i32) -> Self {
+        self.tcp_keepalive_cnt = Some(count);
+        self
+    }
+
+    /// Sets the interval between keepalive probes (`ZMQ_TCP_KEEPALIVE_INTVL`), with a resolution
+    /// of seconds.
+    pub fn tcp_keepalive_interval(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive_intvl = Some(duration_to_secs(interval));
+        self
+    }
+
+    /// Sets the initial interval between reconnection attempts (`ZMQ_RECONNECT_IVL`).
+    pub fn reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_ivl = Some(duration_to_ms(interval));
+        self
+    }
+
+    /// Sets the maximum interval between reconnection attempts (`ZMQ_RECONNECT_IVL_MAX`). The
+    /// interval doubles after every attempt until this maximum is reached.
+    pub fn reconnect_interval_max(mut self, interval: Duration) -> Self {
+        self.reconnect_ivl_max = Some(duration_to_ms(interval));
+        self
+    }
+
+    /// Sets the interval between ZMTP heartbeats (`ZMQ_HEARTBEAT_IVL`). Heartbeats detect dead
+    /// connections that TCP does not notice.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_ivl = Some(duration_to_ms(interval));
+        self
+    }
+
+    /// Sets how long to wait for any traffic after sending a heartbeat before the connection is
+    /// closed (`ZMQ_HEARTBEAT_TIMEOUT`).
+    pub fn heartbeat_timeout(mut self, timeout: Duration) -> Self {
+        self.heartbeat_timeout = Some(duration_to_ms(timeout));
+        self
+    }
+
+    /// Sets the timeout the publisher should use for this connection after receiving a heartbeat
+    /// (`ZMQ_HEARTBEAT_TTL`), with a resolution of 100 milliseconds.
+    pub fn heartbeat_ttl(mut self, ttl: Duration) -> Self {
+        self.heartbeat_ttl = Some(duration_to_ms(ttl));
+        self
+    }
+
+    /// Returns the endpoints that will be subscribed to.
+    pub fn endpoints(&self) -> impl Iterator<Item = &str> {
+        self.endpoints.iter().map(String::as_str)
+    }
+
+    /// Returns the [`Context`] set with [`SubscriberBuilder::context`] or a new one.
+    pub(super) fn context_or_new(&self) -> Context {
+        self.context.clone().unwrap_or_default()
+    }
+
+    /// Creates a SUB socket in `context` with all options applied and subscribed to the
+    /// configured topics, without connecting it.
+    pub(super) fn new_unconnected_socket(&self, context: &Context) -> Result<Socket> {
+        let socket = context.socket(zmq::SUB)?;
+
+        if let Some(hwm) = self.rcvhwm {
+            socket.set_rcvhwm(hwm)?;
+        }
+        if let Some(timeout) = self.rcvtimeo {
+            socket.set_rcvtimeo(timeout)?;
+        }
+        if let Some(enable) = self.tcp_keepalive {
+            socket.set_tcp_keepalive(enable as i32)?;
+        }
+        if let Some(idle) = self.tcp_keepalive_idle {
+            socket.set_tcp_keepalive_idle(idle)?;
+        }
+        if let Some(count) = self.tcp_keepalive_cnt {
+            socket.set_tcp_keepalive_cnt(count)?;
+        }
+        if let Some(interval) = self.tcp_keepalive_intvl {
+            socket.set_tcp_keepalive_intvl(interval)?;
+        }
+        if let Some(interval) = self.reconnect_ivl {
+            socket.set_reconnect_ivl(interval)?;
+        }
+        if let Some(interval) = self.reconnect_ivl_max {
+            socket.set_reconnect_ivl_max(interval)?;
+        }
+        if let Some(interval) = self.heartbeat_ivl {
+            socket.set_heartbeat_ivl(interval)?;
+        }
+        if let Some(timeout) = self.heartbeat_timeout {
+            socket.set_heartbeat_timeout(timeout)?;
+        }
+        if let Some(ttl) = self.heartbeat_ttl {
+            socket.set_heartbeat_ttl(ttl)?;
+        }
+
+        match &self.topics {
+            Some(topics) => {
+                for topic in topics {
+                    socket.set_subscribe(topic.as_bytes())?;
+                }
+            }
+            None => socket.set_subscribe(b"")?,
+        }
+
+        Ok(socket)
+    }
+
+    /// Creates a SUB socket with all options applied, connected to all endpoints.
+    pub(super) fn new_socket(&self) -> Result<(Context, Socket)> {
+        let context = self.context_or_new();
+        let socket = self.new_unconnected_socket(&context)?;
+
+        for endpoint in &self.endpoints {
+            socket.connect(endpoint)?;
+        }
+
+        Ok((context, socket))
+    }
+
+    /// Creates a SUB socket with all options applied, connected to all endpoints, and a PAIR
+    /// socket that receives the events of its monitor. The monitor is started before connecting,
+    /// so no events are missed.
+    pub(super) fn new_monitored_socket(&self) -> Result<(Context, Socket, Socket)> {
+        let context = self.context_or_new();
+        let socket = self.new_unconnected_socket(&context)?;
+
+        let monitor_endpoint = unique_inproc_endpoint("monitor");
+        socket.monitor(&monitor_endpoint, zmq::SocketEvent::ALL as i32)?;
+
+        let monitor = context.socket(zmq::PAIR)?;
+        monitor.connect(&monitor_endpoint)?;
+
+        for endpoint in &self.endpoints {
+            socket.connect(endpoint)?;
+        }
+
+        Ok((context, socket, monitor))
+    }
+
+    /// Creates a SUB socket with all options applied for every endpoint, each connected to only
+    /// that endpoint.
+    pub(super) fn new_sockets(&self) -> Result<(Context, Vec<(String, Socket)>)> {
+        let context = self.context_or_new();
+        let mut sockets = Vec::with_capacity(self.endpoints.len());
+
+        for endpoint in &self.endpoints {
+            let socket = self.new_unconnected_socket(&context)?;
+            socket.connect(endpoint)?;
+
+            sockets.push((endpoint.clone(), socket));
+        }
+
+        Ok((context, sockets))
+    }
+
+    /// Returns the [`CaptureTap`] set with [`SubscriberBuilder::capture`] for a socket connected to
+    /// all endpoints, which records the endpoint only if there is one.
+    pub(super) fn tap(&self) -> Option<Tap> {
+        let capture = self.capture.clone()?;
+        let endpoint = match self.endpoints.as_slice() {
+            [endpoint] => endpoint.clone(),
+            _ => String::new(),
+        };
+
+        Some(Tap { capture, endpoint })
+    }
+
+    /// Returns the receive timeout in milliseconds as a poll timeout (-1 if not set).
+    pub(super) fn poll_timeout(&self) -> i64 {
+        self.rcvtimeo.map_or(-1, i64::from)
+    }
+
+    /// Subscribes and blocks the thread until [`ControlFlow::Break`] is returned by the callback.
+    pub fn subscribe_blocking<F, B>(self, callback: F) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<Message>) -> ControlFlow<B>,
+    {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(subscribe_internal(socket, self.tap(), callback))
+    }
+
+    /// Subscribes and blocks the thread until [`ControlFlow::Break`] is returned by the callback.
+    /// The callback receives [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_blocking<F, B>(self, callback: F) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<RawMessage>) -> ControlFlow<B>,
+    {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(subscribe_internal(socket, self.tap(), callback))
+    }
+
+    /// Subscribes and passes every message to `handler`, blocking the thread until one of its
+    /// methods returns [`ControlFlow::Break`]. [`Handler::on_idle`] is called when the receive
+    /// timeout (see [`SubscriberBuilder::receive_timeout`]) expires.
+    pub fn subscribe_blocking_handler<H, B>(self, handler: H) -> Result<ControlFlow<B, Infallible>>
+    where
+        H: Handler<B>,
+    {
+        self.handler_internal(handler)
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_blocking_handler`], but `handler` receives
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_blocking_handler<H, B>(
+        self,
+        handler: H,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        H: Handler<B, RawMessage>,
+    {
+        self.handler_internal(handler)
+    }
+
+    fn handler_internal<M, H, B>(self, mut handler: H) -> Result<ControlFlow<B, Infallible>>
+    where
+        M: FromParts,
+        H: Handler<B, M>,
+    {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(match self.rcvtimeo {
+            Some(timeout) => subscribe_timeout_internal(
+                socket,
+                self.tap(),
+                timeout.into(),
+                |event| match event {
+                    Ok(SubscribeEvent::Message(message)) => handler.on_message(message),
+                    Ok(SubscribeEvent::Idle) => handler.on_idle(),
+                    Err(err) => handler.on_error(err),
+                },
+            ),
+            None => subscribe_internal(socket, self.tap(), |msg| match msg {
+                Ok(message) => handler.on_message(message),
+                Err(err) => handler.on_error(err),
+            }),
+        })
+    }
+
+    /// Subscribes and blocks the thread until [`ControlFlow::Break`] is returned by the callback.
+    /// The callback is called with [`SubscribeEvent::Idle`] whenever no message was received for
+    /// `timeout`. This timeout replaces the one set with [`SubscriberBuilder::receive_timeout`].
+    pub fn subscribe_blocking_timeout<F, B>(
+        self,
+        timeout: Duration,
+        callback: F,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<SubscribeEvent>) -> ControlFlow<B>,
+    {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(subscribe_timeout_internal(
+            socket,
+            self.tap(),
+            duration_to_ms(timeout).into(),
+            callback,
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_blocking_timeout`], but the callback receives
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_blocking_timeout<F, B>(
+        self,
+        timeout: Duration,
+        callback: F,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<SubscribeEvent<RawMessage>>) -> ControlFlow<B>,
+    {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(subscribe_timeout_internal(
+            socket,
+            self.tap(),
+            duration_to_ms(timeout).into(),
+            callback,
+        ))
+    }
+
+    /// Subscribes and blocks the thread until [`ControlFlow::Break`] is returned by the callback.
+    /// Uses a socket per endpoint so every message can be tagged with the endpoint it was
+    /// received from.
+    pub fn subscribe_blocking_with_source<F, B>(
+        self,
+        callback: F,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<Received>) -> ControlFlow<B>,
+    {
+        let (_context, sockets) = self.new_sockets()?;
+
+        Ok(subscribe_with_source_internal(
+            sockets,
+            self.capture.clone(),
+            self.poll_timeout(),
+            callback,
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_blocking_with_source`], but the callback receives
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_blocking_with_source<F, B>(
+        self,
+        callback: F,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<Received<RawMessage>>) -> ControlFlow<B>,
+    {
+        let (_context, sockets) = self.new_sockets()?;
+
+        Ok(subscribe_with_source_internal(
+            sockets,
+            self.capture.clone(),
+            self.poll_timeout(),
+            callback,
+        ))
+    }
+
+    /// Subscribes and blocks the thread until [`ControlFlow::Break`] is returned by the callback.
+    /// Besides messages, the callback receives the events of the socket's monitor (see
+    /// [`MonitorMessage`][crate::MonitorMessage]).
+    pub fn subscribe_blocking_monitor<F, B>(self, callback: F) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<SocketMessage>) -> ControlFlow<B>,
+    {
+        let (_context, socket, monitor) = self.new_monitored_socket()?;
+
+        Ok(subscribe_monitor_internal(
+            socket,
+            monitor,
+            self.tap(),
+            self.poll_timeout(),
+            callback,
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_blocking_monitor`], but the callback receives
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_blocking_monitor<F, B>(
+        self,
+        callback: F,
+    ) -> Result<ControlFlow<B, Infallible>>
+    where
+        F: FnMut(Result<SocketMessage<RawMessage>>) -> ControlFlow<B>,
+    {
+        let (_context, socket, monitor) = self.new_monitored_socket()?;
+
+        Ok(subscribe_monitor_internal(
+            socket,
+            monitor,
+            self.tap(),
+            self.poll_timeout(),
+            callback,
+        ))
+    }
+
+    /// Subscribes and returns a [`Receiver`]. Messages are received on a background thread.
+    pub fn subscribe_receiver(self) -> Result<Receiver<Result<Message>>> {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(receiver_internal(socket, self.tap()))
+    }
+
+    /// Subscribes and returns a [`Receiver`] of [`RawMessage`]s, which are not decoded. Messages
+    /// are received on a background thread.
+    pub fn subscribe_raw_receiver(self) -> Result<Receiver<Result<RawMessage>>> {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(receiver_internal(socket, self.tap()))
+    }
+
+    /// Subscribes and returns a [`Receiver`] that produces messages and the events of the socket's
+    /// monitor (see [`MonitorMessage`][crate::MonitorMessage]). Both are received on a background
+    /// thread.
+    pub fn subscribe_receiver_monitor(self) -> Result<Receiver<Result<SocketMessage>>> {
+        let (_context, socket, monitor) = self.new_monitored_socket()?;
+
+        Ok(receiver_monitor_internal(
+            socket,
+            monitor,
+            self.tap(),
+            self.poll_timeout(),
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_receiver_monitor`], but the [`Receiver`] produces
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_receiver_monitor(
+        self,
+    ) -> Result<Receiver<Result<SocketMessage<RawMessage>>>> {
+        let (_context, socket, monitor) = self.new_monitored_socket()?;
+
+        Ok(receiver_monitor_internal(
+            socket,
+            monitor,
+            self.tap(),
+            self.poll_timeout(),
+        ))
+    }
+
+    /// Subscribes and waits until the handshake with every endpoint succeeded, then returns a
+    /// [`Receiver`] like [`SubscriberBuilder::subscribe_receiver_monitor`]. If that takes longer
+    /// than `timeout`, a [`Timeout`] with the state of every endpoint is returned.
+    pub fn subscribe_wait_handshake(
+        self,
+        timeout: Duration,
+    ) -> core::result::Result<Result<Receiver<Result<SocketMessage>>>, Timeout> {
+        self.receiver_wait_handshake(timeout)
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_wait_handshake`], but the [`Receiver`] produces
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_wait_handshake(
+        self,
+        timeout: Duration,
+    ) -> core::result::Result<Result<Receiver<Result<SocketMessage<RawMessage>>>>, Timeout> {
+        self.receiver_wait_handshake(timeout)
+    }
+
+    fn receiver_wait_handshake<M: FromParts + Send + 'static>(
+        self,
+        timeout: Duration,
+    ) -> core::result::Result<Result<Receiver<Result<SocketMessage<M>>>>, Timeout> {
+        let mut report = HandshakeReport::new(self.endpoints());
+
+        let (_context, socket, monitor) = match self.new_monitored_socket() {
+            Ok(sockets) => sockets,
+            Err(err) => return Ok(Err(err)),
+        };
+
+        match wait_handshake_internal(&monitor, &mut report, timeout) {
+            Ok(true) => Ok(Ok(receiver_monitor_internal(
+                socket,
+                monitor,
+                self.tap(),
+                self.poll_timeout(),
+            ))),
+            Ok(false) => Err(Timeout::new(report)),
+            Err(err) => Ok(Err(err)),
+        }
+    }
+
+    /// Subscribes and returns a [`BoundedReceiver`] that holds at most `capacity` messages. When
+    /// it is full, `policy` decides which messages are dropped. Messages are received on a
+    /// background thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn subscribe_receiver_bounded(
+        self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<BoundedReceiver> {
+        assert!(capacity > 0, "capacity must be at least 1");
+
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(bounded_receiver_internal(
+            socket,
+            self.tap(),
+            capacity,
+            policy,
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_receiver_bounded`], but the [`BoundedReceiver`]
+    /// produces [`RawMessage`]s, which are not decoded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn subscribe_raw_receiver_bounded(
+        self,
+        capacity: usize,
+        policy: OverflowPolicy,
+    ) -> Result<BoundedReceiver<RawMessage>> {
+        assert!(capacity > 0, "capacity must be at least 1");
+
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(bounded_receiver_internal(
+            socket,
+            self.tap(),
+            capacity,
+            policy,
+        ))
+    }
+
+    /// Subscribes and returns a [`Receiver`] together with a [`SubscriptionHandle`] to stop the
+    /// background thread messages are received on.
+    pub fn subscribe_receiver_with_handle(
+        self,
+    ) -> Result<(Receiver<Result<Message>>, SubscriptionHandle)> {
+        let (context, socket) = self.new_socket()?;
+
+        receiver_with_handle_internal(&context, socket, self.tap(), self.poll_timeout())
+    }
+
+    /// Subscribes and returns a [`Receiver`] of messages tagged with the endpoint they were
+    /// received from. Messages are received on a background thread.
+    pub fn subscribe_receiver_with_source(self) -> Result<Receiver<Result<Received>>> {
+        let (_context, sockets) = self.new_sockets()?;
+
+        Ok(receiver_with_source_internal(
+            sockets,
+            self.capture.clone(),
+            self.poll_timeout(),
+        ))
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_receiver_with_handle`], but the [`Receiver`] produces
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_receiver_with_handle(
+        self,
+    ) -> Result<(Receiver<Result<RawMessage>>, SubscriptionHandle)> {
+        let (context, socket) = self.new_socket()?;
+
+        receiver_with_handle_internal(&context, socket, self.tap(), self.poll_timeout())
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_receiver_with_source`], but the [`Receiver`] produces
+    /// [`RawMessage`]s, which are not decoded.
+    pub fn subscribe_raw_receiver_with_source(
+        self,
+    ) -> Result<Receiver<Result<Received<RawMessage>>>> {
+        let (_context, sockets) = self.new_sockets()?;
+
+        Ok(receiver_with_source_internal(
+            sockets,
+            self.capture.clone(),
+            self.poll_timeout(),
+        ))
+    }
+
+    /// Subscribes and returns a [`Watched`] receiver that reports endpoints that did not publish a
+    /// `hashblock` message within `window` (see [`Watchdog`]). Messages are received on a
+    /// background thread. There is no raw variant: the watchdog only needs the topics, so pair a
+    /// [`Watchdog`] with [`SubscriberBuilder::subscribe_raw_receiver_with_source`] instead.
+    pub fn subscribe_watchdog(self, window: Duration) -> Result<Watched> {
+        let endpoints: Vec<_> = self.endpoints().collect();
+        let watchdog = Watchdog::new(&endpoints, window);
+
+        Ok(Watched::new(
+            self.subscribe_receiver_with_source()?,
+            watchdog,
+        ))
+    }
+
+    /// Subscribes and records every message to `writer` on a background thread, see
+    /// [`Recorder`].
+    pub fn subscribe_recorder<W>(self, writer: CaptureWriter<W>) -> Result<Recorder>
+    where
+        W: Write + Send + 'static,
+    {
+        let (context, sockets) = self.new_sockets()?;
+
+        Recorder::spawn(&context, sockets, CaptureTap::new(writer))
+    }
+
+    /// Subscribes and returns a stream that produces [`Message`]s.
+    #[cfg(feature = "async")]
+    pub fn subscribe_async(self) -> Result<MessageStream> {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(MessageStream::new(socket.into(), self.tap()))
+    }
+
+    /// Subscribes and returns a stream that produces [`RawMessage`]s, which are not decoded.
+    #[cfg(feature = "async")]
+    pub fn subscribe_raw_async(self) -> Result<RawMessageStream> {
+        let (_context, socket) = self.new_socket()?;
+
+        Ok(RawMessageStream::new(socket.into(), self.tap()))
+    }
+
+    /// Subscribes and returns a stream that produces messages tagged with the endpoint they were
+    /// received from.
+    #[cfg(feature = "async")]
+    pub fn subscribe_async_with_source(self) -> Result<SourceMessageStream> {
+        self.async_source_stream()
+    }
+
+    /// Subscribes and returns a stream that produces [`RawMessage`]s, which are not decoded,
+    /// tagged with the endpoint they were received from.
+    #[cfg(feature = "async")]
+    pub fn subscribe_raw_async_with_source(self) -> Result<SourceMessageStream<RawMessage>> {
+        self.async_source_stream()
+    }
+
+    #[cfg(feature = "async")]
+    fn async_source_stream<M>(self) -> Result<SourceMessageStream<M>> {
+        let (_context, sockets) = self.new_sockets()?;
+
+        Ok(SourceMessageStream::new(
+            sockets
+                .into_iter()
+                .map(|(endpoint, socket)| (endpoint, socket.into()))
+                .collect(),
+            self.capture,
+        ))
+    }
+
+    /// Subscribes and returns a stream that yields [`Message`]s and the events of the socket's
+    /// monitor (see [`MonitorMessage`][crate::MonitorMessage]).
+    #[cfg(feature = "async")]
+    pub fn subscribe_async_monitor(self) -> Result<subscribe_async_monitor_stream::MessageStream> {
+        self.async_monitor_stream()
+    }
+
+    /// Like [`SubscriberBuilder::subscribe_async_monitor`], but the stream yields
+    /// [`RawMessage`]s, which are not decoded.
+    #[cfg(feature = "async")]
+    pub fn subscribe_raw_async_monitor(
+        self,
+    ) -> Result<subscribe_async_monitor_stream::MessageStream<RawMessage>> {
+        self.async_monitor_stream()
+    }
+
+    #[cfg(feature = "async")]
+    pub(super) fn async_monitor_stream<M>(
+        self,
+    ) -> Result<subscribe_async_monitor_stream::MessageStream<M>> {
+        let (_context, socket, monitor) = self.new_monitored_socket()?;
+
+        Ok(subscribe_async_monitor_stream::MessageStream::new(
+            socket,
+            monitor,
+            self.tap(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubscriberBuilder;
+    use crate::Topic;
+    use core::time::Duration;
+
+    #[test]
+    fn socket_options() {
+        let (_context, socket) = SubscriberBuilder::new(&["tcp://127.0.0.1:28332"])
+            .topics(&[Topic::HashBlock])
+            .receive_high_water_mark(1234)
+            .receive_timeout(Duration::from_millis(250))
+            .tcp_keepalive(true)
+            .tcp_keepalive_idle(Duration::from_secs(30))
+            .tcp_keepalive_count(3)
+            .tcp_keepalive_interval(Duration::from_secs(5))
+            .reconnect_interval(Duration::from_millis(200))
+            .reconnect_interval_max(Duration::from_secs(10))
+            .heartbeat_interval(Duration::from_secs(1))
+            .heartbeat_timeout(Duration::from_secs(3))
+            .heartbeat_ttl(Duration::from_secs(6))
+            .new_socket()
+            .unwrap();
+
+        assert_eq!(socket.get_rcvhwm().unwrap(), 1234);
+        assert_eq!(socket.get_rcvtimeo().unwrap(), 250);
+        assert_eq!(socket.get_tcp_keepalive().unwrap(), 1);
+        assert_eq!(socket.get_tcp_keepalive_idle().unwrap(), 30);
+        assert_eq!(socket.get_tcp_keepalive_cnt().unwrap(), 3);
+        assert_eq!(socket.get_tcp_keepalive_intvl().unwrap(), 5);
+        assert_eq!(socket.get_reconnect_ivl().unwrap(), 200);
+        assert_eq!(socket.get_reconnect_ivl_max().unwrap(), 10_000);
+        assert_eq!(socket.get_heartbeat_ivl().unwrap(), 1000);
+        assert_eq!(socket.get_heartbeat_timeout().unwrap(), 3000);
+        assert_eq!(socket.get_heartbeat_ttl().unwrap(), 6000);
+    }
+}
@@ -0,0 +1,105 @@
+use super::unique_inproc_endpoint;
+use crate::error::Result;
+use std::{
+    panic,
+    thread::{self, JoinHandle},
+};
+use zmq::{Context, Socket};
+
+/// Handle to the background thread of a subscription, returned by
+/// [`subscribe_receiver_with_handle`][crate::subscribe_receiver_with_handle].
+///
+/// The thread waits for a message on the subscription's socket(s) and on an internal control
+/// socket at the same time, so [`SubscriptionHandle::shutdown`] stops it right away, even if the
+/// publisher is quiet. When the thread exits, its sockets are closed.
+///
+/// Dropping the handle detaches the thread, it then keeps running until the [`Receiver`] is
+/// dropped and the next message arrives.
+///
+/// [`Receiver`]: std::sync::mpsc::Receiver
+pub struct SubscriptionHandle {
+    control: Socket,
+    thread: JoinHandle<()>,
+}
+
+impl SubscriptionHandle {
+    /// Creates a control socket pair in `context` and runs `f` on a new thread with the receiving
+    /// end. `f` should return as soon as a message is received on it.
+    pub(crate) fn spawn<F>(context: &Context, f: F) -> Result<Self>
+    where
+        F: FnOnce(Socket) + Send + 'static,
+    {
+        let endpoint = unique_inproc_endpoint("shutdown");
+
+        let thread_control = context.socket(zmq::PAIR)?;
+        thread_control.bind(&endpoint)?;
+
+        let control = context.socket(zmq::PAIR)?;
+        control.connect(&endpoint)?;
+
+        let thread = thread::spawn(move || f(thread_control));
+
+        Ok(Self { control, thread })
+    }
+
+    /// Signals the background thread to stop. This does not wait for it to exit, use
+    /// [`SubscriptionHandle::join`] for that. Messages that are already received can still be
+    /// read from the [`Receiver`][std::sync::mpsc::Receiver].
+    pub fn shutdown(&self) -> Result<()> {
+        match self.control.send(&[] as &[u8], zmq::DONTWAIT) {
+            // the thread already exited or was already signaled
+            Ok(()) | Err(zmq::Error::EAGAIN) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Returns true if the background thread exited.
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
+    /// Waits for the background thread to exit. Call [`SubscriptionHandle::shutdown`] first,
+    /// otherwise this only returns when the [`Receiver`][std::sync::mpsc::Receiver] is dropped
+    /// and the next message arrives.
+    #[inline]
+    pub fn join(self) -> thread::Result<()> {
+        self.thread.join()
+    }
+
+    /// Signals the background thread to stop and waits for it to exit.
+    #[inline]
+    pub fn shutdown_and_join(self) -> Result<()> {
+        self.shutdown()?;
+        if let Err(panic) = self.join() {
+            panic::resume_unwind(panic);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::SubscriberBuilder;
+    use core::time::Duration;
+
+    #[test]
+    fn shutdown() {
+        let context = zmq::Context::new();
+        let publisher = context.socket(zmq::PUB).unwrap();
+        publisher.bind("inproc://handle-shutdown").unwrap();
+
+        let (rx, handle) = SubscriberBuilder::new(&["inproc://handle-shutdown"])
+            .context(context)
+            .subscribe_receiver_with_handle()
+            .unwrap();
+
+        // nothing is published, the thread is blocked until it is signaled
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+        assert!(!handle.is_finished());
+
+        handle.shutdown_and_join().unwrap();
+
+        assert!(rx.recv().is_err());
+    }
+}
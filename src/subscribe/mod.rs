@@ -1,27 +1,42 @@
 pub mod blocking;
+pub mod bounded;
+pub mod builder;
+pub mod handle;
 pub mod receiver;
 #[cfg(feature = "async")]
 pub mod stream;
 
 use crate::{
+    capture::tap::CaptureTap,
     error::Result,
     message::{Message, SEQUENCE_LEN, TOPIC_MAX_LEN},
+    monitor::{handshake::HandshakeReport, MonitorMessage, SocketMessage},
+    raw_message::RawMessage,
+    received::Received,
+    topic::Topic,
     Error, DATA_MAX_LEN,
 };
-use core::{cmp::min, convert::Infallible, ops::ControlFlow, slice};
-use zmq::{Context, Socket};
-
-pub(super) fn new_socket_internal(endpoints: &[&str]) -> Result<(Context, Socket)> {
-    let context = Context::new();
+use blocking::SubscribeEvent;
+use core::{
+    cmp::min,
+    convert::Infallible,
+    ops::ControlFlow,
+    slice,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use std::time::Instant;
+use zmq::Socket;
 
-    let socket = context.socket(zmq::SUB)?;
-    socket.set_subscribe(b"")?;
+/// Returns an `inproc://` endpoint that is unique within this process, so multiple subscribers
+/// can share a ZMQ context.
+pub(crate) fn unique_inproc_endpoint(kind: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
 
-    for endpoint in endpoints {
-        socket.connect(endpoint)?;
-    }
-
-    Ok((context, socket))
+    format!(
+        "inproc://ghostcore-zmq-{kind}-{}",
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
 }
 
 pub(super) trait ReceiveFrom {
@@ -55,10 +70,42 @@ impl ReceiveFrom for slice::Iter<'_, zmq::Message> {
     }
 }
 
-pub(super) fn recv_internal<R: ReceiveFrom>(
+/// A message created from the parts of a received multipart, either decoded ([`Message`]) or only
+/// checked ([`RawMessage`]).
+pub(crate) trait FromParts: Sized {
+    fn from_parts(topic: &[u8], data: &[u8], seq: [u8; SEQUENCE_LEN]) -> Result<Self>;
+
+    fn topic_type(&self) -> Topic;
+}
+
+impl FromParts for Message {
+    #[inline]
+    fn from_parts(topic: &[u8], data: &[u8], seq: [u8; SEQUENCE_LEN]) -> Result<Self> {
+        Message::from_parts(topic, data, seq)
+    }
+
+    #[inline]
+    fn topic_type(&self) -> Topic {
+        Message::topic_type(self)
+    }
+}
+
+impl FromParts for RawMessage {
+    #[inline]
+    fn from_parts(topic: &[u8], data: &[u8], seq: [u8; SEQUENCE_LEN]) -> Result<Self> {
+        RawMessage::from_parts(topic, data, seq)
+    }
+
+    #[inline]
+    fn topic_type(&self) -> Topic {
+        self.topic
+    }
+}
+
+pub(super) fn recv_internal<R: ReceiveFrom, M: FromParts>(
     mut socket: R,
     data: &mut [u8; DATA_MAX_LEN],
-) -> Result<Message> {
+) -> Result<M> {
     let mut topic = [0u8; TOPIC_MAX_LEN];
     let mut sequence = [0u8; SEQUENCE_LEN];
 
@@ -86,7 +133,7 @@ pub(super) fn recv_internal<R: ReceiveFrom>(
     }
 
     if !socket.has_next()? {
-        return Message::from_parts(&topic[0..topic_len], &data[0..data_len], sequence);
+        return M::from_parts(&topic[0..topic_len], &data[0..data_len], sequence);
     }
 
     let mut len = 3;
@@ -102,29 +149,273 @@ pub(super) fn recv_internal<R: ReceiveFrom>(
     }
 }
 
-pub(super) fn subscribe_internal<F, B>(socket: Socket, callback: F) -> ControlFlow<B, Infallible>
+/// A [`CaptureTap`] (see [`SubscriberBuilder::capture`]) together with the endpoint to record
+/// the messages of a single socket with.
+///
+/// [`SubscriberBuilder::capture`]: builder::SubscriberBuilder::capture
+#[derive(Clone)]
+pub(super) struct Tap {
+    pub(super) capture: CaptureTap,
+    pub(super) endpoint: String,
+}
+
+impl Tap {
+    /// Splits an optional tap into the arguments of [`poll_internal`] and the endpoint to use for
+    /// the socket.
+    pub(super) fn into_poll_parts(tap: Option<Self>) -> (Option<CaptureTap>, String) {
+        tap.map_or((None, String::new()), |tap| {
+            (Some(tap.capture), tap.endpoint)
+        })
+    }
+}
+
+/// Receives a message from `socket` like [`recv_internal`]. If there is a tap, the multipart is
+/// recorded to it with `endpoint` before it is decoded.
+pub(super) fn recv_tapped<M: FromParts>(
+    socket: &Socket,
+    tap: Option<(&CaptureTap, &str)>,
+    data: &mut [u8; DATA_MAX_LEN],
+) -> Result<M> {
+    let Some((capture, endpoint)) = tap else {
+        return recv_internal(socket, data);
+    };
+
+    let mut parts = vec![socket.recv_msg(0)?];
+    while socket.get_rcvmore()? {
+        parts.push(socket.recv_msg(0)?);
+    }
+
+    capture.record(endpoint, &parts);
+
+    recv_internal(parts.iter(), data)
+}
+
+pub(super) fn subscribe_internal<M, F, B>(
+    socket: Socket,
+    tap: Option<Tap>,
+    mut callback: F,
+) -> ControlFlow<B, Infallible>
 where
-    F: Fn(Result<Message>) -> ControlFlow<B>,
+    M: FromParts,
+    F: FnMut(Result<M>) -> ControlFlow<B>,
 {
     let mut data: Box<[u8; DATA_MAX_LEN]> =
         vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap();
+    let tap = tap
+        .as_ref()
+        .map(|tap| (&tap.capture, tap.endpoint.as_str()));
 
     loop {
-        let msg = recv_internal(&socket, &mut data);
+        let msg = recv_tapped(&socket, tap, &mut data);
 
         callback(msg)?;
     }
 }
 
+/// Like [`subscribe_internal`], but polls a separate socket for every endpoint so every message
+/// can be tagged with its source. `timeout` is the poll timeout in milliseconds (-1 to wait
+/// indefinitely), when it expires a [`zmq::Error::EAGAIN`] error is passed to the callback, the
+/// same as a receive timeout on a single socket.
+pub(super) fn subscribe_with_source_internal<M, F, B>(
+    sockets: Vec<(String, Socket)>,
+    capture: Option<CaptureTap>,
+    timeout: i64,
+    mut callback: F,
+) -> ControlFlow<B, Infallible>
+where
+    M: FromParts,
+    F: FnMut(Result<Received<M>>) -> ControlFlow<B>,
+{
+    poll_uncontrolled(&sockets, capture.as_ref(), timeout, |msg| {
+        callback(msg.unwrap_or_else(|| Err(zmq::Error::EAGAIN.into())))
+    })
+}
+
+/// Receives a multipart message from a monitor socket and parses it.
+pub(super) fn recv_monitor_message(monitor: &Socket) -> Result<MonitorMessage> {
+    let mut parts = vec![monitor.recv_msg(0)?];
+
+    while monitor.get_rcvmore()? {
+        parts.push(monitor.recv_msg(0)?);
+    }
+
+    Ok(MonitorMessage::parse_from(&parts)?)
+}
+
+/// Like [`subscribe_internal`], but also passes the events received on `monitor` to the callback.
+/// `timeout` is the poll timeout in milliseconds (-1 to wait indefinitely), when it expires a
+/// [`zmq::Error::EAGAIN`] error is passed to the callback.
+pub(super) fn subscribe_monitor_internal<M, F, B>(
+    socket: Socket,
+    monitor: Socket,
+    tap: Option<Tap>,
+    timeout: i64,
+    mut callback: F,
+) -> ControlFlow<B, Infallible>
+where
+    M: FromParts,
+    F: FnMut(Result<SocketMessage<M>>) -> ControlFlow<B>,
+{
+    let mut data: Box<[u8; DATA_MAX_LEN]> =
+        vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap();
+    let tap = tap
+        .as_ref()
+        .map(|tap| (&tap.capture, tap.endpoint.as_str()));
+
+    let mut items = [
+        socket.as_poll_item(zmq::POLLIN),
+        monitor.as_poll_item(zmq::POLLIN),
+    ];
+
+    loop {
+        match zmq::poll(&mut items, timeout) {
+            Ok(0) => callback(Err(zmq::Error::EAGAIN.into()))?,
+            Ok(_) => {
+                // events first, a connection is established before messages arrive through it
+                if items[1].is_readable() {
+                    callback(recv_monitor_message(&monitor).map(SocketMessage::Event))?;
+                }
+                if items[0].is_readable() {
+                    callback(recv_tapped(&socket, tap, &mut data).map(SocketMessage::Message))?;
+                }
+            }
+            Err(err) => callback(Err(err.into()))?,
+        }
+    }
+}
+
+/// Receives events from `monitor` until the handshake with every endpoint in `report` succeeded.
+/// Returns `Ok(false)` if that did not happen within `timeout`.
+pub(super) fn wait_handshake_internal(
+    monitor: &Socket,
+    report: &mut HandshakeReport,
+    timeout: Duration,
+) -> Result<bool> {
+    let deadline = Instant::now() + timeout;
+
+    while !report.is_complete() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(false);
+        }
+
+        // round up, so the deadline has passed when poll times out
+        let remaining_ms = remaining.as_nanos().div_ceil(1_000_000);
+        let mut items = [monitor.as_poll_item(zmq::POLLIN)];
+        if zmq::poll(&mut items, remaining_ms.try_into().unwrap_or(i64::MAX))? > 0 {
+            report.on_event(&recv_monitor_message(monitor)?);
+        }
+    }
+
+    Ok(true)
+}
+
+/// Like [`subscribe_internal`], but passes [`SubscribeEvent::Idle`] to the callback when no
+/// message was received within `timeout` milliseconds.
+pub(super) fn subscribe_timeout_internal<M, F, B>(
+    socket: Socket,
+    tap: Option<Tap>,
+    timeout: i64,
+    mut callback: F,
+) -> ControlFlow<B, Infallible>
+where
+    M: FromParts,
+    F: FnMut(Result<SubscribeEvent<M>>) -> ControlFlow<B>,
+{
+    let (capture, endpoint) = Tap::into_poll_parts(tap);
+
+    poll_uncontrolled(&[(endpoint, socket)], capture.as_ref(), timeout, |msg| {
+        callback(match msg {
+            Some(msg) => msg.map(|received| SubscribeEvent::Message(received.message)),
+            None => Ok(SubscribeEvent::Idle),
+        })
+    })
+}
+
+/// [`poll_internal`] without a control socket, this only returns when the callback returns
+/// [`ControlFlow::Break`].
+fn poll_uncontrolled<M, F, B>(
+    sockets: &[(String, Socket)],
+    capture: Option<&CaptureTap>,
+    timeout: i64,
+    callback: F,
+) -> ControlFlow<B, Infallible>
+where
+    M: FromParts,
+    F: FnMut(Option<Result<Received<M>>>) -> ControlFlow<B>,
+{
+    match poll_internal(sockets, None, capture, timeout, callback) {
+        ControlFlow::Break(b) => ControlFlow::Break(b),
+        ControlFlow::Continue(()) => unreachable!("no control socket to stop polling"),
+    }
+}
+
+/// Polls `sockets` and passes every message to the callback, until the callback returns
+/// [`ControlFlow::Break`] or a message is received on `control`, in which case
+/// [`ControlFlow::Continue`] is returned. [`None`] is passed to the callback when no message was
+/// received within `timeout` milliseconds. If there is a tap, every multipart is recorded to it
+/// with the endpoint of its socket.
+pub(crate) fn poll_internal<M, F, B>(
+    sockets: &[(String, Socket)],
+    control: Option<&Socket>,
+    capture: Option<&CaptureTap>,
+    timeout: i64,
+    mut callback: F,
+) -> ControlFlow<B>
+where
+    M: FromParts,
+    F: FnMut(Option<Result<Received<M>>>) -> ControlFlow<B>,
+{
+    let mut data: Box<[u8; DATA_MAX_LEN]> =
+        vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap();
+
+    let mut items: Vec<_> = sockets
+        .iter()
+        .map(|(_, socket)| socket)
+        .chain(control)
+        .map(|socket| socket.as_poll_item(zmq::POLLIN))
+        .collect();
+
+    loop {
+        match zmq::poll(&mut items, timeout) {
+            Ok(0) => callback(None)?,
+            Ok(_) => {
+                if control.is_some() && items[sockets.len()].is_readable() {
+                    return ControlFlow::Continue(());
+                }
+
+                for (item, (endpoint, socket)) in items.iter().zip(sockets) {
+                    if item.is_readable() {
+                        let tap = capture.map(|capture| (capture, endpoint.as_str()));
+                        let msg = recv_tapped(socket, tap, &mut data).map(|message| Received {
+                            endpoint: endpoint.clone(),
+                            message,
+                        });
+
+                        callback(Some(msg))?;
+                    }
+                }
+            }
+            Err(err) => callback(Some(Err(err.into())))?,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{subscribe::recv_internal, Error, DATA_MAX_LEN};
+    use crate::{
+        subscribe::recv_internal, subscribe_wait_handshake, testing::MockPublisher, EndpointState,
+        Error, Handler, Message, RawMessage, SocketEvent, SocketMessage, SubscribeEvent,
+        SubscriberBuilder, Topic, DATA_MAX_LEN,
+    };
+    use bitcoin::{hashes::Hash, BlockHash};
+    use core::{ops::ControlFlow, time::Duration};
 
     #[test]
     fn test_invalid_data_len() {
         const LEN: usize = 8 * 1024 * 1024;
         let megabytes = vec![0; LEN];
-        let multipart = vec![
+        let multipart = [
             zmq::Message::from("topic"),
             zmq::Message::from(megabytes),
             zmq::Message::from(&[0x00u8, 0x00, 0x00, 0x00] as &[u8]),
@@ -133,8 +424,244 @@ mod tests {
         let mut temp_buffer: Box<[u8; DATA_MAX_LEN]> =
             vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap();
         assert!(matches!(
-            recv_internal(multipart.iter(), &mut temp_buffer),
+            recv_internal::<_, Message>(multipart.iter(), &mut temp_buffer),
             Err(Error::InvalidDataLength(LEN))
         ));
     }
+
+    #[test]
+    fn test_receive_with_source() {
+        let context = zmq::Context::new();
+        let endpoints = ["inproc://source-a", "inproc://source-b"];
+
+        let publishers: Vec<_> = endpoints
+            .iter()
+            .map(|endpoint| {
+                let socket = context.socket(zmq::PUB).unwrap();
+                socket.bind(endpoint).unwrap();
+                socket
+            })
+            .collect();
+
+        let rx = SubscriberBuilder::new(&endpoints)
+            .context(context)
+            .subscribe_receiver_with_source()
+            .unwrap();
+
+        for (i, (endpoint, publisher)) in endpoints.iter().zip(&publishers).enumerate() {
+            let msg = Message::HashBlock(BlockHash::from_byte_array([i as u8; 32]), 0);
+
+            // subscriptions are propagated asynchronously, resend until one arrives
+            let received = loop {
+                publisher
+                    .send_multipart(msg.serialize_to_vecs(), 0)
+                    .unwrap();
+                if let Ok(received) = rx.recv_timeout(Duration::from_millis(10)) {
+                    break received.unwrap();
+                }
+            };
+
+            assert_eq!(received.endpoint, *endpoint);
+            assert_eq!(received.message, msg);
+
+            // drain resent copies
+            while let Ok(received) = rx.recv_timeout(Duration::from_millis(50)) {
+                assert_eq!(received.unwrap().endpoint, *endpoint);
+            }
+        }
+    }
+
+    #[test]
+    fn test_blocking_timeout() {
+        let context = zmq::Context::new();
+        let publisher = context.socket(zmq::PUB).unwrap();
+        publisher.bind("inproc://blocking-timeout").unwrap();
+
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        let mut idle = 0;
+
+        let result = SubscriberBuilder::new(&["inproc://blocking-timeout"])
+            .context(context)
+            .subscribe_blocking_timeout(Duration::from_millis(10), |event| match event.unwrap() {
+                SubscribeEvent::Idle => {
+                    idle += 1;
+                    // publish only when idle, this also covers the subscription not being
+                    // propagated yet
+                    publisher
+                        .send_multipart(msg.serialize_to_vecs(), 0)
+                        .unwrap();
+                    ControlFlow::Continue(())
+                }
+                SubscribeEvent::Message(received) => ControlFlow::Break(received),
+            })
+            .unwrap();
+
+        assert_eq!(result, ControlFlow::Break(msg));
+        assert!(idle > 0);
+    }
+
+    #[test]
+    fn test_blocking_handler() {
+        struct Collect {
+            publisher: zmq::Socket,
+            messages: Vec<Message>,
+        }
+
+        impl Handler<u32> for Collect {
+            fn on_message(&mut self, message: Message) -> ControlFlow<u32> {
+                self.messages.push(message);
+                if self.messages.len() == 3 {
+                    ControlFlow::Break(3)
+                } else {
+                    ControlFlow::Continue(())
+                }
+            }
+
+            fn on_idle(&mut self) -> ControlFlow<u32> {
+                let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+                self.publisher
+                    .send_multipart(msg.serialize_to_vecs(), 0)
+                    .unwrap();
+                ControlFlow::Continue(())
+            }
+        }
+
+        let context = zmq::Context::new();
+        let publisher = context.socket(zmq::PUB).unwrap();
+        publisher.bind("inproc://blocking-handler").unwrap();
+
+        let mut handler = Collect {
+            publisher,
+            messages: Vec::new(),
+        };
+
+        let result = SubscriberBuilder::new(&["inproc://blocking-handler"])
+            .context(context)
+            .receive_timeout(Duration::from_millis(10))
+            .subscribe_blocking_handler(&mut handler)
+            .unwrap();
+
+        assert_eq!(result, ControlFlow::Break(3));
+        assert_eq!(handler.messages.len(), 3);
+    }
+
+    #[test]
+    fn test_receiver_monitor() {
+        let publisher = zmq::Context::new().socket(zmq::PUB).unwrap();
+        publisher.bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = publisher.get_last_endpoint().unwrap().unwrap();
+
+        let rx = SubscriberBuilder::new(&[&endpoint])
+            .subscribe_receiver_monitor()
+            .unwrap();
+
+        let mut events = Vec::new();
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+
+        let received = loop {
+            match rx.recv_timeout(Duration::from_millis(10)) {
+                Ok(Ok(SocketMessage::Event(event))) => {
+                    assert_eq!(event.source_url, endpoint);
+                    events.push(event.event);
+                }
+                Ok(Ok(SocketMessage::Message(received))) => break received,
+                Ok(Err(err)) => panic!("{err}"),
+                Err(_) => publisher
+                    .send_multipart(msg.serialize_to_vecs(), 0)
+                    .unwrap(),
+            }
+        };
+
+        assert_eq!(received, msg);
+        // the monitor is started before connecting, so no events are missed
+        assert_eq!(events[0], SocketEvent::ConnectDelayed);
+        assert!(matches!(events[1], SocketEvent::Connected { .. }));
+        assert!(events.contains(&SocketEvent::HandshakeSucceeded));
+    }
+
+    #[test]
+    fn test_wait_handshake() {
+        let publisher = zmq::Context::new().socket(zmq::PUB).unwrap();
+        publisher.bind("tcp://127.0.0.1:*").unwrap();
+        let endpoint = publisher.get_last_endpoint().unwrap().unwrap();
+
+        // nothing listens on this port
+        let closed = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("tcp://{}", listener.local_addr().unwrap())
+        };
+
+        let timeout = SubscriberBuilder::new(&[&endpoint, &closed])
+            .reconnect_interval(Duration::from_millis(10))
+            .subscribe_wait_handshake(Duration::from_millis(200))
+            .unwrap_err();
+
+        let report = timeout.report();
+        assert_eq!(
+            report.get(&endpoint).unwrap().state,
+            EndpointState::HandshakeSucceeded
+        );
+        assert_eq!(
+            report.pending().map(|r| &r.endpoint).collect::<Vec<_>>(),
+            [&closed]
+        );
+        // ZMQ adds a random amount to the reconnect interval
+        assert!(matches!(
+            report.get(&closed).unwrap().state,
+            EndpointState::Retrying { .. }
+        ));
+        assert!(timeout.to_string().contains("retrying every"));
+
+        let rx = subscribe_wait_handshake(&[&endpoint], Duration::from_secs(10))
+            .unwrap()
+            .unwrap();
+
+        // the subscription may not have reached the publisher yet, so publish until received
+        let msg = Message::HashBlock(BlockHash::all_zeros(), 0);
+        let received = loop {
+            match rx.recv_timeout(Duration::from_millis(10)) {
+                Ok(Ok(SocketMessage::Message(received))) => break received,
+                Ok(Ok(SocketMessage::Event(event))) => panic!("unexpected event {event:?}"),
+                Ok(Err(err)) => panic!("{err}"),
+                Err(_) => publisher
+                    .send_multipart(msg.serialize_to_vecs(), 0)
+                    .unwrap(),
+            }
+        };
+        assert_eq!(received, msg);
+    }
+
+    #[test]
+    fn test_raw() {
+        let publisher = MockPublisher::inproc().unwrap();
+        let rx = publisher.subscriber().subscribe_raw_receiver().unwrap();
+        assert!(publisher
+            .wait_for_subscription(Duration::from_secs(10))
+            .unwrap());
+
+        let block = Message::HashBlock(BlockHash::all_zeros(), 1);
+        publisher.forward(&block).unwrap();
+        publisher
+            .send_raw(&[b"rawblock".as_slice(), &[0xff; 10], &[2, 0, 0, 0]])
+            .unwrap();
+        publisher
+            .send_raw(&[b"rawblock".as_slice(), &[0xff; 10], &[2, 0, 0]])
+            .unwrap();
+
+        let raw = rx.recv().unwrap().unwrap();
+        assert_eq!(raw, RawMessage::from(&block));
+        assert_eq!(raw.decode().unwrap(), block);
+
+        // the data is not decoded, but the framing is still checked
+        let raw = rx.recv().unwrap().unwrap();
+        assert_eq!((raw.topic, raw.sequence), (Topic::RawBlock, 2));
+        assert!(matches!(
+            raw.decode(),
+            Err(Error::BitcoinDeserialization(_))
+        ));
+        assert!(matches!(
+            rx.recv().unwrap(),
+            Err(Error::InvalidSequenceLength(3))
+        ));
+    }
 }
@@ -1,10 +1,26 @@
-use super::{new_socket_internal, subscribe_internal};
-use crate::{error::Result, message::Message};
-use core::ops::ControlFlow;
+use super::{
+    bounded::{bounded_channel, BoundedReceiver, OverflowPolicy},
+    builder::SubscriberBuilder,
+    handle::SubscriptionHandle,
+    poll_internal, subscribe_internal, subscribe_monitor_internal, subscribe_with_source_internal,
+    FromParts, Tap,
+};
+use crate::{
+    capture::tap::CaptureTap,
+    error::Result,
+    message::Message,
+    monitor::{handshake::Timeout, SocketMessage},
+    raw_message::RawMessage,
+    received::Received,
+    topic::Topic,
+    watchdog::Watched,
+};
+use core::{ops::ControlFlow, time::Duration};
 use std::{
     sync::mpsc::{channel, Receiver},
     thread,
 };
+use zmq::{Context, Socket};
 
 fn break_on_err(is_err: bool) -> ControlFlow<()> {
     if is_err {
@@ -37,11 +53,253 @@ pub fn subscribe_multi(endpoints: &[&str]) -> Result<Receiver<Result<Message>>>
 /// Subscribes to multiple ZMQ endpoints and returns a [`Receiver`].
 #[inline]
 pub fn subscribe_receiver(endpoints: &[&str]) -> Result<Receiver<Result<Message>>> {
+    SubscriberBuilder::new(endpoints).subscribe_receiver()
+}
+
+/// Subscribes to multiple ZMQ endpoints, only receiving messages of the given topics, and returns
+/// a [`Receiver`]. See [`SubscriberBuilder::topics`].
+///
+/// # Panics
+///
+/// Panics if `topics` is empty.
+#[inline]
+pub fn subscribe_receiver_topics(
+    endpoints: &[&str],
+    topics: &[Topic],
+) -> Result<Receiver<Result<Message>>> {
+    SubscriberBuilder::new(endpoints)
+        .topics(topics)
+        .subscribe_receiver()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`Receiver`] of messages tagged with the
+/// endpoint they were received from.
+#[inline]
+pub fn subscribe_receiver_with_source(endpoints: &[&str]) -> Result<Receiver<Result<Received>>> {
+    SubscriberBuilder::new(endpoints).subscribe_receiver_with_source()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`Receiver`] of [`RawMessage`]s, which are
+/// not decoded.
+#[inline]
+pub fn subscribe_raw_receiver(endpoints: &[&str]) -> Result<Receiver<Result<RawMessage>>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_receiver()
+}
+
+/// Like [`subscribe_receiver_with_source`], but the [`Receiver`] produces [`RawMessage`]s, which
+/// are not decoded.
+#[inline]
+pub fn subscribe_raw_receiver_with_source(
+    endpoints: &[&str],
+) -> Result<Receiver<Result<Received<RawMessage>>>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_receiver_with_source()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`Receiver`] together with a
+/// [`SubscriptionHandle`] to stop the background thread.
+#[inline]
+pub fn subscribe_receiver_with_handle(
+    endpoints: &[&str],
+) -> Result<(Receiver<Result<Message>>, SubscriptionHandle)> {
+    SubscriberBuilder::new(endpoints).subscribe_receiver_with_handle()
+}
+
+/// Like [`subscribe_receiver_with_handle`], but the [`Receiver`] produces [`RawMessage`]s, which
+/// are not decoded.
+#[inline]
+pub fn subscribe_raw_receiver_with_handle(
+    endpoints: &[&str],
+) -> Result<(Receiver<Result<RawMessage>>, SubscriptionHandle)> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_receiver_with_handle()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`Receiver`] that produces [`Message`]s and
+/// events (see [`MonitorMessage`][crate::MonitorMessage]).
+#[inline]
+pub fn subscribe_receiver_monitor(endpoints: &[&str]) -> Result<Receiver<Result<SocketMessage>>> {
+    SubscriberBuilder::new(endpoints).subscribe_receiver_monitor()
+}
+
+/// Like [`subscribe_receiver_monitor`], but the [`Receiver`] produces [`RawMessage`]s, which are
+/// not decoded.
+#[inline]
+pub fn subscribe_raw_receiver_monitor(
+    endpoints: &[&str],
+) -> Result<Receiver<Result<SocketMessage<RawMessage>>>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_receiver_monitor()
+}
+
+/// Subscribes to multiple ZMQ endpoints and waits until the handshake with every endpoint
+/// succeeded, then returns a [`Receiver`] that produces [`Message`]s and events (see
+/// [`MonitorMessage`][crate::MonitorMessage]). If that takes longer than `timeout`, a [`Timeout`]
+/// is returned that reports the state of every endpoint (for example, whether ZMQ is still
+/// retrying to connect or the handshake failed).
+///
+/// Only `tcp://` and `ipc://` endpoints produce handshake events, for other transports this
+/// always times out.
+///
+/// ```no_run
+/// use core::time::Duration;
+/// use ghostcore_zmq::subscribe_wait_handshake;
+///
+/// let rx = match subscribe_wait_handshake(&["tcp://127.0.0.1:28332"], Duration::from_secs(2)) {
+///     Ok(rx) => rx.unwrap(),
+///     Err(timeout) => {
+///         for endpoint in timeout.report().pending() {
+///             println!("unable to connect to {endpoint}");
+///         }
+///         return;
+///     }
+/// };
+/// ```
+#[inline]
+pub fn subscribe_wait_handshake(
+    endpoints: &[&str],
+    timeout: Duration,
+) -> core::result::Result<Result<Receiver<Result<SocketMessage>>>, Timeout> {
+    SubscriberBuilder::new(endpoints).subscribe_wait_handshake(timeout)
+}
+
+/// Like [`subscribe_wait_handshake`], but the [`Receiver`] produces [`RawMessage`]s, which are
+/// not decoded.
+#[inline]
+pub fn subscribe_raw_wait_handshake(
+    endpoints: &[&str],
+    timeout: Duration,
+) -> core::result::Result<Result<Receiver<Result<SocketMessage<RawMessage>>>>, Timeout> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_wait_handshake(timeout)
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`Watched`] receiver that reports
+/// endpoints that did not publish a `hashblock` message within `window` (see
+/// [`Watchdog`][crate::Watchdog]). There is no raw variant, see
+/// [`SubscriberBuilder::subscribe_watchdog`].
+#[inline]
+pub fn subscribe_watchdog(endpoints: &[&str], window: Duration) -> Result<Watched> {
+    SubscriberBuilder::new(endpoints).subscribe_watchdog(window)
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a [`BoundedReceiver`] that holds at most
+/// `capacity` messages. When it is full, `policy` decides which messages are dropped.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+#[inline]
+pub fn subscribe_receiver_bounded(
+    endpoints: &[&str],
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> Result<BoundedReceiver> {
+    SubscriberBuilder::new(endpoints).subscribe_receiver_bounded(capacity, policy)
+}
+
+/// Like [`subscribe_receiver_bounded`], but the [`BoundedReceiver`] produces [`RawMessage`]s,
+/// which are not decoded.
+///
+/// # Panics
+///
+/// Panics if `capacity` is 0.
+#[inline]
+pub fn subscribe_raw_receiver_bounded(
+    endpoints: &[&str],
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> Result<BoundedReceiver<RawMessage>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_receiver_bounded(capacity, policy)
+}
+
+pub(super) fn receiver_internal<M>(socket: Socket, tap: Option<Tap>) -> Receiver<Result<M>>
+where
+    M: FromParts + Send + 'static,
+{
     let (tx, rx) = channel();
 
-    let (_context, socket) = new_socket_internal(endpoints)?;
+    thread::spawn(move || {
+        subscribe_internal(socket, tap, |msg| break_on_err(tx.send(msg).is_err()))
+    });
+
+    rx
+}
+
+pub(super) fn bounded_receiver_internal<M>(
+    socket: Socket,
+    tap: Option<Tap>,
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> BoundedReceiver<M>
+where
+    M: FromParts + Send + 'static,
+{
+    let (tx, rx) = bounded_channel(capacity, policy);
+
+    thread::spawn(move || {
+        subscribe_internal(socket, tap, |msg| break_on_err(tx.send(msg).is_err()))
+    });
+
+    rx
+}
+
+pub(super) fn receiver_monitor_internal<M>(
+    socket: Socket,
+    monitor: Socket,
+    tap: Option<Tap>,
+    timeout: i64,
+) -> Receiver<Result<SocketMessage<M>>>
+where
+    M: FromParts + Send + 'static,
+{
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        subscribe_monitor_internal(socket, monitor, tap, timeout, |msg| {
+            break_on_err(tx.send(msg).is_err())
+        })
+    });
+
+    rx
+}
+
+pub(super) fn receiver_with_source_internal<M>(
+    sockets: Vec<(String, Socket)>,
+    capture: Option<CaptureTap>,
+    timeout: i64,
+) -> Receiver<Result<Received<M>>>
+where
+    M: FromParts + Send + 'static,
+{
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+        subscribe_with_source_internal(sockets, capture, timeout, |msg| {
+            break_on_err(tx.send(msg).is_err())
+        })
+    });
+
+    rx
+}
+
+pub(super) fn receiver_with_handle_internal<M>(
+    context: &Context,
+    socket: Socket,
+    tap: Option<Tap>,
+    timeout: i64,
+) -> Result<(Receiver<Result<M>>, SubscriptionHandle)>
+where
+    M: FromParts + Send + 'static,
+{
+    let (tx, rx) = channel();
+
+    let handle = SubscriptionHandle::spawn(context, move |control| {
+        let (capture, endpoint) = Tap::into_poll_parts(tap);
+        let sockets = [(endpoint, socket)];
+
+        let _ = poll_internal(&sockets, Some(&control), capture.as_ref(), timeout, |msg| {
+            let msg = msg.unwrap_or_else(|| Err(zmq::Error::EAGAIN.into()));
 
-    thread::spawn(move || subscribe_internal(socket, |msg| break_on_err(tx.send(msg).is_err())));
+            break_on_err(tx.send(msg.map(Received::into_message)).is_err())
+        });
+    })?;
 
-    Ok(rx)
+    Ok((rx, handle))
 }
@@ -1,12 +1,20 @@
-use super::new_socket_internal;
+use super::{builder::SubscriberBuilder, recv_internal, FromParts};
 use crate::{
+    capture::tap::CaptureTap,
     error::Result,
-    message::Message,
-    monitor::{event::SocketEvent, MonitorMessage, MonitorMessageError},
+    message::{Message, DATA_MAX_LEN},
+    monitor::{
+        handshake::{HandshakeReport, Timeout},
+        MonitorMessage, SocketMessage,
+    },
+    raw_message::RawMessage,
+    received::Received,
+    topic::Topic,
 };
+use async_zmq::Subscribe;
 use core::{
-    fmt,
     future::Future,
+    marker::PhantomData,
     mem,
     pin::{pin, Pin},
     slice,
@@ -22,13 +30,6 @@ use std::{
     thread,
 };
 
-/// A [`Message`] or a [`MonitorMessage`].
-#[derive(Debug, Clone)]
-pub enum SocketMessage {
-    Message(Message),
-    Event(MonitorMessage),
-}
-
 /// Stream that asynchronously produces [`Message`]s using multiple ZMQ subscribers. The ZMQ
 /// sockets are polled in a round-robin fashion.
 #[deprecated(
@@ -93,10 +94,11 @@ pub mod subscribe_async_stream {
     use crate::{
         error::Result,
         message::{Message, DATA_MAX_LEN},
-        subscribe::recv_internal,
+        subscribe::{recv_internal, FromParts, Tap},
     };
     use async_zmq::Subscribe;
     use core::{
+        marker::PhantomData,
         pin::Pin,
         task::{Context as AsyncContext, Poll},
     };
@@ -105,17 +107,28 @@ pub mod subscribe_async_stream {
         Stream,
     };
 
-    /// Stream returned by [`subscribe_async`][super::subscribe_async].
-    pub struct MessageStream {
+    /// Also exported at the crate root, kept here so the error of
+    /// [`subscribe_async_wait_handshake_timeout`][super::subscribe_async_wait_handshake_timeout]
+    /// can still be named from this module.
+    pub use crate::monitor::handshake::Timeout;
+
+    /// Stream returned by [`subscribe_async`][super::subscribe_async]. With `M` set to
+    /// [`RawMessage`][crate::RawMessage] (see [`RawMessageStream`][super::RawMessageStream]), the messages are not
+    /// decoded.
+    pub struct MessageStream<M = Message> {
         zmq_stream: Subscribe,
         data_cache: Box<[u8; DATA_MAX_LEN]>,
+        tap: Option<Tap>,
+        _message: PhantomData<fn() -> M>,
     }
 
-    impl MessageStream {
-        pub(super) fn new(zmq_stream: Subscribe) -> Self {
+    impl<M> MessageStream<M> {
+        pub(crate) fn new(zmq_stream: Subscribe, tap: Option<Tap>) -> Self {
             Self {
                 zmq_stream,
                 data_cache: vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap(),
+                tap,
+                _message: PhantomData,
             }
         }
 
@@ -129,8 +142,8 @@ pub mod subscribe_async_stream {
         }
     }
 
-    impl Stream for MessageStream {
-        type Item = Result<Message>;
+    impl<M: FromParts> Stream for MessageStream<M> {
+        type Item = Result<M>;
 
         fn poll_next(
             mut self: Pin<&mut Self>,
@@ -138,14 +151,19 @@ pub mod subscribe_async_stream {
         ) -> Poll<Option<Self::Item>> {
             self.zmq_stream.poll_next_unpin(cx).map(|opt| {
                 Some(match opt.unwrap() {
-                    Ok(mp) => recv_internal(mp.iter(), &mut self.data_cache),
+                    Ok(mp) => {
+                        if let Some(tap) = &self.tap {
+                            tap.capture.record(&tap.endpoint, &mp);
+                        }
+                        recv_internal(mp.iter(), &mut self.data_cache)
+                    }
                     Err(err) => Err(err.into()),
                 })
             })
         }
     }
 
-    impl FusedStream for MessageStream {
+    impl<M: FromParts> FusedStream for MessageStream<M> {
         fn is_terminated(&self) -> bool {
             false
         }
@@ -154,14 +172,131 @@ pub mod subscribe_async_stream {
 
 /// Subscribes to multiple ZMQ endpoints and returns a stream that produces [`Message`]s.
 pub fn subscribe_async(endpoints: &[&str]) -> Result<subscribe_async_stream::MessageStream> {
-    let (_context, socket) = new_socket_internal(endpoints)?;
+    SubscriberBuilder::new(endpoints).subscribe_async()
+}
+
+/// Subscribes to multiple ZMQ endpoints, only receiving messages of the given topics, and returns
+/// a stream that produces [`Message`]s. See [`SubscriberBuilder::topics`].
+///
+/// # Panics
+///
+/// Panics if `topics` is empty.
+pub fn subscribe_async_topics(
+    endpoints: &[&str],
+    topics: &[Topic],
+) -> Result<subscribe_async_stream::MessageStream> {
+    SubscriberBuilder::new(endpoints)
+        .topics(topics)
+        .subscribe_async()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a stream that produces [`Message`]s tagged
+/// with the endpoint they were received from.
+pub fn subscribe_async_with_source(endpoints: &[&str]) -> Result<SourceMessageStream> {
+    SubscriberBuilder::new(endpoints).subscribe_async_with_source()
+}
+
+/// Subscribes to multiple ZMQ endpoints and returns a stream that produces [`RawMessage`]s, which
+/// are not decoded, tagged with the endpoint they were received from.
+pub fn subscribe_raw_async_with_source(
+    endpoints: &[&str],
+) -> Result<SourceMessageStream<RawMessage>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_async_with_source()
+}
+
+/// Stream returned by [`subscribe_async_with_source`] that produces [`Message`]s tagged with the
+/// endpoint they were received from. Every endpoint has its own ZMQ socket, the sockets are
+/// polled in a round-robin fashion. Without endpoints, the stream ends immediately.
+pub struct SourceMessageStream<M = Message> {
+    streams: Vec<(String, Subscribe)>,
+    next: usize,
+    data_cache: Box<[u8; DATA_MAX_LEN]>,
+    capture: Option<CaptureTap>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M> SourceMessageStream<M> {
+    pub(crate) fn new(streams: Vec<(String, Subscribe)>, capture: Option<CaptureTap>) -> Self {
+        Self {
+            streams,
+            next: 0,
+            data_cache: vec![0; DATA_MAX_LEN].into_boxed_slice().try_into().unwrap(),
+            capture,
+            _message: PhantomData,
+        }
+    }
+
+    /// Returns the endpoints and references to the ZMQ sockets used by this stream. This is
+    /// useful to set socket options or use other functions provided by [`zmq`] or [`async_zmq`].
+    /// (See `MessageStream::as_zmq_socket`)
+    pub fn as_zmq_sockets(&self) -> impl Iterator<Item = (&str, &Subscribe)> {
+        self.streams
+            .iter()
+            .map(|(endpoint, stream)| (endpoint.as_str(), stream))
+    }
+}
+
+impl<M: FromParts> Stream for SourceMessageStream<M> {
+    type Item = Result<Received<M>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut AsyncContext<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let len = this.streams.len();
+
+        // without sockets there is nothing that could wake us up
+        if len == 0 {
+            return Poll::Ready(None);
+        }
+
+        for i in 0..len {
+            let index = (this.next + i) % len;
+            let (endpoint, stream) = &mut this.streams[index];
+
+            if let Poll::Ready(opt) = stream.poll_next_unpin(cx) {
+                this.next = (index + 1) % len;
+
+                return Poll::Ready(Some(match opt.unwrap() {
+                    Ok(mp) => {
+                        if let Some(capture) = &this.capture {
+                            capture.record(endpoint, &mp);
+                        }
+                        recv_internal(mp.iter(), &mut this.data_cache).map(|message| Received {
+                            endpoint: endpoint.clone(),
+                            message,
+                        })
+                    }
+                    Err(err) => Err(err.into()),
+                }));
+            }
+        }
+
+        Poll::Pending
+    }
+}
+
+impl<M: FromParts> FusedStream for SourceMessageStream<M> {
+    fn is_terminated(&self) -> bool {
+        self.streams.is_empty()
+    }
+}
 
-    Ok(subscribe_async_stream::MessageStream::new(socket.into()))
+/// Subscribes to multiple ZMQ endpoints and returns a stream that produces [`RawMessage`]s, which
+/// are not decoded.
+pub fn subscribe_raw_async(endpoints: &[&str]) -> Result<RawMessageStream> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_async()
 }
 
+/// Stream returned by [`subscribe_raw_async`] that produces [`RawMessage`]s.
+pub type RawMessageStream = subscribe_async_stream::MessageStream<RawMessage>;
+
 pub mod subscribe_async_monitor_stream {
     use super::{subscribe_async_stream, SocketMessage};
-    use crate::{error::Result, monitor::MonitorMessage};
+    use crate::{
+        error::Result,
+        message::Message,
+        monitor::MonitorMessage,
+        subscribe::{FromParts, Tap},
+    };
     use async_zmq::Subscribe;
     use core::{
         pin::Pin,
@@ -193,18 +328,20 @@ pub mod subscribe_async_monitor_stream {
     // Better to use an empty type to not waste precious bytes
     pub(super) type RecvOnlyPair = async_zmq::Pair<Empty, Empty>;
 
-    /// Stream returned by [`subscribe_async_monitor`][super::subscribe_async_monitor].
-    pub struct MessageStream {
-        messages: subscribe_async_stream::MessageStream,
+    /// Stream returned by [`subscribe_async_monitor`][super::subscribe_async_monitor] (and
+    /// [`subscribe_raw_async_monitor`][super::subscribe_raw_async_monitor], which produces
+    /// [`RawMessage`][crate::RawMessage]s).
+    pub struct MessageStream<M = Message> {
+        messages: subscribe_async_stream::MessageStream<M>,
         pub(super) monitor: RecvOnlyPair,
     }
 
-    impl MessageStream {
-        pub(super) fn new(
-            messages: subscribe_async_stream::MessageStream,
-            monitor: RecvOnlyPair,
-        ) -> Self {
-            Self { messages, monitor }
+    impl<M> MessageStream<M> {
+        pub(crate) fn new(socket: Socket, monitor: Socket, tap: Option<Tap>) -> Self {
+            Self {
+                messages: subscribe_async_stream::MessageStream::new(socket.into(), tap),
+                monitor: monitor.into(),
+            }
         }
 
         /// Returns a reference to the ZMQ socket used by this stream. To get the [`zmq::Socket`], use
@@ -223,8 +360,8 @@ pub mod subscribe_async_monitor_stream {
         }
     }
 
-    impl Stream for MessageStream {
-        type Item = Result<SocketMessage>;
+    impl<M: FromParts> Stream for MessageStream<M> {
+        type Item = Result<SocketMessage<M>>;
 
         fn poll_next(
             mut self: Pin<&mut Self>,
@@ -245,7 +382,7 @@ pub mod subscribe_async_monitor_stream {
         }
     }
 
-    impl FusedStream for MessageStream {
+    impl<M: FromParts> FusedStream for MessageStream<M> {
         fn is_terminated(&self) -> bool {
             false
         }
@@ -253,97 +390,107 @@ pub mod subscribe_async_monitor_stream {
 }
 
 /// Subscribes to multiple ZMQ endpoints and returns a stream that yields [`Message`]s and events
-/// (see [`MonitorMessage`]).
+/// (see [`MonitorMessage`][crate::MonitorMessage]).
 pub fn subscribe_async_monitor(
     endpoints: &[&str],
 ) -> Result<subscribe_async_monitor_stream::MessageStream> {
-    let (context, socket) = new_socket_internal(endpoints)?;
-
-    socket.monitor("inproc://monitor", zmq::SocketEvent::ALL as i32)?;
-
-    let monitor = context.socket(zmq::PAIR)?;
-    monitor.connect("inproc://monitor")?;
-
-    Ok(subscribe_async_monitor_stream::MessageStream::new(
-        subscribe_async_stream::MessageStream::new(socket.into()),
-        monitor.into(),
-    ))
+    SubscriberBuilder::new(endpoints).subscribe_async_monitor()
 }
 
-// TODO have some way to extract connecting to which endpoints failed, now just a (unit) error is returned (by tokio::time::timeout)
+/// Like [`subscribe_async_monitor`], but the stream yields [`RawMessage`]s, which are not decoded.
+pub fn subscribe_raw_async_monitor(
+    endpoints: &[&str],
+) -> Result<subscribe_async_monitor_stream::MessageStream<RawMessage>> {
+    SubscriberBuilder::new(endpoints).subscribe_raw_async_monitor()
+}
 
 /// Subscribes to multiple ZMQ endpoints and returns a stream that yields [`Message`]s and events
-/// (see [`MonitorMessage`]). This method will wait until a connection has been established to all
+/// (see [`MonitorMessage`][crate::MonitorMessage]). This method will wait until a connection has been established to all
 /// endpoints.
 ///
 /// See examples/subscribe_async_timeout.rs for a usage example.
 ///
 /// **NOTE:** This method will wait indefinitely until a connection has been established, but this is
 /// often undesirable. This method should therefore be used in combination with your async
-/// runtime's timeout function. Currently, with the state of async Rust in December of 2023, it is
-/// not yet possible do this without creating an extra thread per timeout or depending on specific
-/// runtimes.
+/// runtime's timeout function, or use [`subscribe_async_wait_handshake_timeout`], which also
+/// reports the state of every endpoint when it times out. Currently, with the state of async Rust
+/// in December of 2023, it is not yet possible do this without creating an extra thread per
+/// timeout or depending on specific runtimes.
 pub async fn subscribe_async_wait_handshake(
     endpoints: &[&str],
 ) -> Result<subscribe_async_monitor_stream::MessageStream> {
-    let mut stream = subscribe_async_monitor(endpoints)?;
-    let mut connecting = endpoints.len();
+    wait_handshake(
+        endpoints,
+        &mut HandshakeReport::new(endpoints.iter().copied()),
+    )
+    .await
+}
 
-    if connecting == 0 {
-        return Ok(stream);
-    }
+/// Like [`subscribe_async_wait_handshake`], but the stream yields [`RawMessage`]s, which are not
+/// decoded.
+pub async fn subscribe_raw_async_wait_handshake(
+    endpoints: &[&str],
+) -> Result<subscribe_async_monitor_stream::MessageStream<RawMessage>> {
+    wait_handshake(
+        endpoints,
+        &mut HandshakeReport::new(endpoints.iter().copied()),
+    )
+    .await
+}
 
-    loop {
+async fn wait_handshake<M>(
+    endpoints: &[&str],
+    report: &mut HandshakeReport,
+) -> Result<subscribe_async_monitor_stream::MessageStream<M>> {
+    let mut stream = SubscriberBuilder::new(endpoints).async_monitor_stream()?;
+
+    while !report.is_complete() {
         let msg: &[zmq::Message] = &stream.monitor.next().await.unwrap()?;
-        let [event_message, _] = msg else {
-            return Err(MonitorMessageError::InvalidMutlipartLength(msg.len()).into());
-        };
-        match SocketEvent::parse_from(event_message)? {
-            SocketEvent::HandshakeSucceeded => {
-                connecting -= 1;
-            }
-            SocketEvent::Disconnected { .. } => {
-                connecting += 1;
-            }
-            _ => {
-                continue;
-            }
-        }
-        if connecting == 0 {
-            return Ok(stream);
-        }
+        report.on_event(&MonitorMessage::parse_from(msg)?);
     }
+
+    Ok(stream)
 }
 
 /// See [`subscribe_async_wait_handshake`]. This method implements the inefficient, but runtime
-/// independent approach.
+/// independent approach. On timeout, the returned [`Timeout`] reports the state of every endpoint.
 pub async fn subscribe_async_wait_handshake_timeout(
     endpoints: &[&str],
     timeout: Duration,
 ) -> core::result::Result<Result<subscribe_async_monitor_stream::MessageStream>, Timeout> {
-    let subscribe = subscribe_async_wait_handshake(endpoints);
-    let timeout = sleep(timeout);
+    wait_handshake_timeout(endpoints, timeout).await
+}
 
-    match select(pin!(subscribe), timeout).await {
-        Either::Left((res, _)) => Ok(res),
-        Either::Right(_) => Err(Timeout(())),
-    }
+/// Like [`subscribe_async_wait_handshake_timeout`], but the stream yields [`RawMessage`]s, which
+/// are not decoded.
+pub async fn subscribe_raw_async_wait_handshake_timeout(
+    endpoints: &[&str],
+    timeout: Duration,
+) -> core::result::Result<Result<subscribe_async_monitor_stream::MessageStream<RawMessage>>, Timeout>
+{
+    wait_handshake_timeout(endpoints, timeout).await
 }
 
-/// Error returned by [`subscribe_async_wait_handshake_timeout`] when the connection times out.
-/// Contains no information, but does have a Error, Debug and Display impl.
-#[derive(Debug)]
-pub struct Timeout(());
+async fn wait_handshake_timeout<M>(
+    endpoints: &[&str],
+    timeout: Duration,
+) -> core::result::Result<Result<subscribe_async_monitor_stream::MessageStream<M>>, Timeout> {
+    let mut report = HandshakeReport::new(endpoints.iter().copied());
 
-impl fmt::Display for Timeout {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "connection timed out")
-    }
-}
+    let res = {
+        let subscribe = wait_handshake(endpoints, &mut report);
+        let timeout = sleep(timeout);
 
-impl std::error::Error for Timeout {}
+        match select(pin!(subscribe), timeout).await {
+            Either::Left((res, _)) => Some(res),
+            Either::Right(_) => None,
+        }
+    };
+
+    res.ok_or_else(|| Timeout::new(report))
+}
 
-fn sleep(dur: Duration) -> Sleep {
+pub(crate) fn sleep(dur: Duration) -> Sleep {
     let state = Arc::new(Mutex::new(SleepReadyState::Pending));
     {
         let state = state.clone();
@@ -368,7 +515,7 @@ enum SleepReadyState {
     Done,
 }
 
-struct Sleep(Arc<Mutex<SleepReadyState>>);
+pub(crate) struct Sleep(Arc<Mutex<SleepReadyState>>);
 
 impl Future for Sleep {
     type Output = ();
@@ -383,3 +530,19 @@ impl Future for Sleep {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::SubscriberBuilder;
+    use futures_util::{stream::FusedStream, FutureExt, StreamExt};
+
+    #[test]
+    fn source_stream_without_endpoints() {
+        let mut stream = SubscriberBuilder::new(&[])
+            .subscribe_async_with_source()
+            .unwrap();
+
+        assert!(stream.is_terminated());
+        assert!(matches!(stream.next().now_or_never(), Some(None)));
+    }
+}
@@ -0,0 +1,286 @@
+//! Utilities to test code that uses this crate without running a node.
+//!
+//! [`MockPublisher`] binds a socket on an `inproc://` or loopback endpoint and publishes scripted
+//! [`Message`]s, malformed multipart messages and restarts, so the behaviour of a subscriber can
+//! be asserted deterministically in plain `cargo test`.
+//!
+//! ```
+//! use bitcoin::{hashes::Hash, BlockHash};
+//! use core::time::Duration;
+//! use ghostcore_zmq::{testing::MockPublisher, Error, Message};
+//!
+//! let mut publisher = MockPublisher::inproc().unwrap();
+//! let rx = publisher.subscriber().subscribe_receiver().unwrap();
+//! assert!(publisher.wait_for_subscription(Duration::from_secs(10)).unwrap());
+//!
+//! publisher
+//!     .send(&Message::HashBlock(BlockHash::all_zeros(), 0))
+//!     .unwrap();
+//! publisher.send_raw(&[b"hashblock", b"too short"]).unwrap();
+//!
+//! assert_eq!(rx.recv().unwrap().unwrap().sequence(), 0);
+//! assert!(matches!(
+//!     rx.recv().unwrap(),
+//!     Err(Error::InvalidMutlipartLength(2))
+//! ));
+//! ```
+
+use crate::{
+    error::Result, message::Message, publish::Publisher, subscribe::builder::SubscriberBuilder,
+    subscribe::unique_inproc_endpoint,
+};
+use core::time::Duration;
+use std::{thread, time::Instant};
+use zmq::{Context, Socket};
+
+/// A step of a script played by [`MockPublisher::play`].
+#[derive(Debug, Clone)]
+pub enum Step {
+    /// Sends the message with the next sequence number of its topic, see [`Publisher::send`].
+    Send(Message),
+    /// Sends the message with its own sequence number, see [`Publisher::forward`].
+    Forward(Message),
+    /// Sends a multipart message as is, for example one that is malformed.
+    Raw(Vec<Vec<u8>>),
+    /// Restarts the publisher, see [`MockPublisher::restart`].
+    Restart,
+    /// Waits for a subscription, see [`MockPublisher::wait_for_subscription`].
+    WaitForSubscription(Duration),
+    Sleep(Duration),
+}
+
+/// Emulates the ZMQ publisher of a node. See the [module documentation](self) for an example.
+///
+/// Subscriptions are sent to publishers asynchronously, so messages published right after
+/// subscribing are lost. To avoid this, call [`MockPublisher::wait_for_subscription`] before
+/// sending the first message. This works because the publisher uses an XPUB socket, which
+/// receives the subscriptions of its subscribers.
+pub struct MockPublisher {
+    context: Context,
+    endpoint: String,
+    publisher: Publisher,
+}
+
+fn bind_xpub(context: &Context, endpoint: &str) -> Result<Socket> {
+    let socket = context.socket(zmq::XPUB)?;
+    socket.set_linger(0)?;
+    socket.bind(endpoint)?;
+    Ok(socket)
+}
+
+impl MockPublisher {
+    /// Binds to a unique `inproc://` endpoint. Subscribers must use the same ZMQ context, use
+    /// [`MockPublisher::subscriber`] to create them.
+    pub fn inproc() -> Result<Self> {
+        let context = Context::new();
+        let endpoint = unique_inproc_endpoint("mock");
+        let socket = bind_xpub(&context, &endpoint)?;
+
+        Ok(Self {
+            context,
+            endpoint,
+            publisher: Publisher::from_socket(socket),
+        })
+    }
+
+    /// Binds to a free port on `127.0.0.1`. Unlike `inproc://` endpoints, subscribers receive
+    /// monitor events (see [`MonitorMessage`][crate::MonitorMessage]) and reconnect after
+    /// [`MockPublisher::restart`].
+    pub fn tcp() -> Result<Self> {
+        let context = Context::new();
+        let socket = bind_xpub(&context, "tcp://127.0.0.1:*")?;
+        let publisher = Publisher::from_socket(socket);
+
+        Ok(Self {
+            context,
+            endpoint: publisher.last_endpoint()?,
+            publisher,
+        })
+    }
+
+    /// Returns the endpoint subscribers should connect to.
+    #[inline]
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns the ZMQ context of the publisher's socket.
+    #[inline]
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    /// Returns a [`SubscriberBuilder`] for this publisher's endpoint that uses its ZMQ context.
+    pub fn subscriber(&self) -> SubscriberBuilder {
+        SubscriberBuilder::new(&[&self.endpoint]).context(self.context.clone())
+    }
+
+    /// Returns the [`Publisher`] used to send messages, to get or set its sequence numbers.
+    #[inline]
+    pub fn publisher(&mut self) -> &mut Publisher {
+        &mut self.publisher
+    }
+
+    /// Waits until a subscriber subscribes to a new topic (or to all topics), returning false if
+    /// that did not happen within `timeout`. Messages sent afterwards are received by that
+    /// subscriber.
+    pub fn wait_for_subscription(&self, timeout: Duration) -> Result<bool> {
+        let socket = self.publisher.as_zmq_socket();
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut items = [socket.as_poll_item(zmq::POLLIN)];
+
+            if zmq::poll(
+                &mut items,
+                remaining.as_millis().try_into().unwrap_or(i64::MAX),
+            )? == 0
+            {
+                return Ok(false);
+            }
+
+            // the first byte is 1 for a subscription, 0 for an unsubscription
+            if socket.recv_bytes(0)?.first() == Some(&1) {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Sends `msg` with the next sequence number of its topic, see [`Publisher::send`].
+    #[inline]
+    pub fn send(&mut self, msg: &Message) -> Result<u32> {
+        self.publisher.send(msg)
+    }
+
+    /// Sends `msg` with its own sequence number, see [`Publisher::forward`].
+    #[inline]
+    pub fn forward(&self, msg: &Message) -> Result<()> {
+        self.publisher.forward(msg)
+    }
+
+    /// Sends a multipart message as is. Use this to send malformed messages, like ones with the
+    /// wrong number of parts, an unknown topic or invalid data.
+    pub fn send_raw<T: AsRef<[u8]>>(&self, parts: &[T]) -> Result<()> {
+        Ok(self
+            .publisher
+            .as_zmq_socket()
+            .send_multipart(parts.iter().map(AsRef::as_ref), 0)?)
+    }
+
+    /// Emulates a restart of the node: closes the socket, disconnecting all subscribers, and binds
+    /// a new one to the same endpoint, with all sequence numbers reset to 0. Subscribers to a
+    /// `tcp://` endpoint reconnect automatically, call [`MockPublisher::wait_for_subscription`]
+    /// to wait for that.
+    pub fn restart(&mut self) -> Result<()> {
+        // the old socket must be closed before its endpoint can be bound again
+        let socket = self.context.socket(zmq::XPUB)?;
+        drop(core::mem::replace(
+            &mut self.publisher,
+            Publisher::from_socket(socket),
+        ));
+
+        // closing happens in the background, retry until the endpoint is free
+        let mut attempts = 0;
+        let socket = loop {
+            match bind_xpub(&self.context, &self.endpoint) {
+                Err(crate::Error::Zmq(zmq::Error::EADDRINUSE)) if attempts < 100 => {
+                    attempts += 1;
+                    thread::sleep(Duration::from_millis(10));
+                }
+                res => break res?,
+            }
+        };
+
+        self.publisher = Publisher::from_socket(socket);
+
+        Ok(())
+    }
+
+    /// Plays a script.
+    pub fn play<I: IntoIterator<Item = Step>>(&mut self, steps: I) -> Result<()> {
+        for step in steps {
+            match step {
+                Step::Send(msg) => {
+                    self.send(&msg)?;
+                }
+                Step::Forward(msg) => self.forward(&msg)?,
+                Step::Raw(parts) => self.send_raw(&parts)?,
+                Step::Restart => self.restart()?,
+                Step::WaitForSubscription(timeout) => {
+                    self.wait_for_subscription(timeout)?;
+                }
+                Step::Sleep(duration) => thread::sleep(duration),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MockPublisher, Step};
+    use crate::{Error, Message, SocketEvent, SocketMessage, Topic};
+    use bitcoin::{hashes::Hash, BlockHash};
+    use core::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    #[test]
+    fn script() {
+        let block = Message::HashBlock(BlockHash::all_zeros(), 7);
+
+        let mut publisher = MockPublisher::inproc().unwrap();
+        let rx = publisher.subscriber().subscribe_receiver().unwrap();
+
+        publisher
+            .play([
+                Step::WaitForSubscription(TIMEOUT),
+                Step::Send(block.clone()),
+                Step::Raw(vec![b"unknown".to_vec(), vec![0; 32], vec![0; 4]]),
+                Step::Forward(block.clone()),
+                Step::Send(block.clone()),
+            ])
+            .unwrap();
+
+        assert_eq!(rx.recv().unwrap().unwrap().sequence(), 0);
+        assert!(matches!(rx.recv().unwrap(), Err(Error::InvalidTopic(7, _))));
+        assert_eq!(rx.recv().unwrap().unwrap(), block);
+        assert_eq!(rx.recv().unwrap().unwrap().sequence(), 1);
+        assert_eq!(publisher.publisher().next_sequence(Topic::HashBlock), 2);
+    }
+
+    #[test]
+    fn restart() {
+        let block = Message::HashBlock(BlockHash::all_zeros(), 0);
+
+        let mut publisher = MockPublisher::tcp().unwrap();
+        let rx = publisher
+            .subscriber()
+            .reconnect_interval(Duration::from_millis(10))
+            .subscribe_receiver_monitor()
+            .unwrap();
+
+        assert!(publisher.wait_for_subscription(TIMEOUT).unwrap());
+        publisher.send(&block).unwrap();
+        publisher.restart().unwrap();
+        assert!(publisher.wait_for_subscription(TIMEOUT).unwrap());
+        publisher.send(&block).unwrap();
+
+        let mut sequences = Vec::new();
+        let mut disconnected = false;
+        while sequences.len() < 2 {
+            match rx.recv_timeout(TIMEOUT).unwrap().unwrap() {
+                SocketMessage::Message(msg) => sequences.push(msg.sequence()),
+                SocketMessage::Event(event) => {
+                    disconnected |= matches!(event.event, SocketEvent::Disconnected { .. })
+                }
+            }
+        }
+
+        assert!(disconnected);
+        // the sequence numbers start at 0 again after a restart
+        assert_eq!(sequences, [0, 0]);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::message::TOPIC_MAX_LEN;
+use core::fmt;
+
+/// A topic Ghost Core publishes messages on. Used to select which messages to receive when
+/// subscribing (see [`subscribe_blocking_topics`][crate::subscribe_blocking_topics]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Topic {
+    HashBlock,
+    HashTx,
+    HashWTx,
+    RawBlock,
+    RawTx,
+    Sequence,
+}
+
+impl Topic {
+    /// All topics, in the order of their declaration.
+    pub const ALL: [Topic; 6] = [
+        Self::HashBlock,
+        Self::HashTx,
+        Self::HashWTx,
+        Self::RawBlock,
+        Self::RawTx,
+        Self::Sequence,
+    ];
+
+    /// Returns this [`Topic`] as a string slice, as it is sent over the wire.
+    #[inline]
+    pub fn as_str(self) -> &'static str {
+        let topic = match self {
+            Self::HashBlock => "hashblock",
+            Self::HashTx => "hashtx",
+            Self::HashWTx => "hashwtx",
+            Self::RawBlock => "rawblock",
+            Self::RawTx => "rawtx",
+            Self::Sequence => "sequence",
+        };
+
+        debug_assert!(topic.len() <= TOPIC_MAX_LEN);
+
+        topic
+    }
+
+    /// Returns this [`Topic`] as a byte slice, as it is sent over the wire.
+    #[inline]
+    pub fn as_bytes(self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+
+    /// Returns the [`Topic`] with the given name, or [`None`] if no topic has this name.
+    #[inline]
+    pub fn from_bytes(topic: &[u8]) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_bytes() == topic)
+    }
+}
+
+impl fmt::Display for Topic {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Topic;
+
+    #[test]
+    fn names() {
+        for topic in Topic::ALL {
+            assert_eq!(Topic::from_bytes(topic.as_bytes()), Some(topic));
+            assert_eq!(topic.to_string(), topic.as_str());
+        }
+
+        assert_eq!(Topic::from_bytes(b"rawblock"), Some(Topic::RawBlock));
+        assert_eq!(Topic::from_bytes(b"hash"), None);
+        assert_eq!(Topic::from_bytes(b""), None);
+
+        // ZMQ subscriptions are prefix matches, no topic may be a prefix of another one
+        for a in Topic::ALL {
+            for b in Topic::ALL {
+                assert!(a == b || !b.as_bytes().starts_with(a.as_bytes()));
+            }
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use bitcoin::{
+    absolute::LockTime,
+    consensus::{encode, Decodable, Encodable},
+    hashes::Hash,
+    script::{read_scriptint, Instruction},
+    Amount, ScriptBuf, TxIn, Txid, Witness, Wtxid,
+};
+use std::io;
+
+/// The first byte of every Ghost (Particl style) transaction is at least this version.
+pub const GHOST_TX_VERSION: u8 = 0xa0;
+
+/// Length of a Pedersen commitment or public key in a confidential output.
+pub const COMMITMENT_LEN: usize = 33;
+
+/// A transaction as serialized by Ghost Core. Unlike Bitcoin transactions, the version is split in
+/// a version byte and a type byte and outputs are typed (see [`TxOutput`]).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GhostTransaction {
+    pub version: u8,
+    pub tx_type: u8,
+    pub lock_time: LockTime,
+    pub input: Vec<TxIn>,
+    pub output: Vec<TxOutput>,
+}
+
+/// A typed transaction output.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum TxOutput {
+    Standard(OutputStandard),
+    Ct(OutputCT),
+    RingCt(OutputRingCT),
+    Data(OutputData),
+}
+
+/// A plain output with a public value and a script.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutputStandard {
+    pub value: Amount,
+    pub script_pubkey: ScriptBuf,
+}
+
+/// A blind output, the value is hidden in a Pedersen commitment.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutputCT {
+    pub commitment: [u8; COMMITMENT_LEN],
+    pub data: Vec<u8>,
+    pub script_pubkey: ScriptBuf,
+    pub range_proof: Vec<u8>,
+}
+
+/// An anon (RingCT) output, both the value and the receiver are hidden.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutputRingCT {
+    pub pubkey: [u8; COMMITMENT_LEN],
+    pub commitment: [u8; COMMITMENT_LEN],
+    pub data: Vec<u8>,
+    pub range_proof: Vec<u8>,
+}
+
+/// An output that only carries data, used for fees of confidential transactions, coinstake
+/// metadata and narrations.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutputData {
+    pub data: Vec<u8>,
+}
+
+impl TxOutput {
+    pub const TYPE_STANDARD: u8 = 1;
+    pub const TYPE_CT: u8 = 2;
+    pub const TYPE_RINGCT: u8 = 3;
+    pub const TYPE_DATA: u8 = 4;
+
+    /// Returns the type byte that precedes this output when serialized.
+    #[inline]
+    pub fn output_type(&self) -> u8 {
+        match self {
+            Self::Standard(_) => Self::TYPE_STANDARD,
+            Self::Ct(_) => Self::TYPE_CT,
+            Self::RingCt(_) => Self::TYPE_RINGCT,
+            Self::Data(_) => Self::TYPE_DATA,
+        }
+    }
+
+    /// Returns the public value of this output, only [`TxOutput::Standard`] outputs have one.
+    #[inline]
+    pub fn value(&self) -> Option<Amount> {
+        match self {
+            Self::Standard(out) => Some(out.value),
+            _ => None,
+        }
+    }
+
+    /// Returns the script of this output, if it has one.
+    #[inline]
+    pub fn script_pubkey(&self) -> Option<&ScriptBuf> {
+        match self {
+            Self::Standard(out) => Some(&out.script_pubkey),
+            Self::Ct(out) => Some(&out.script_pubkey),
+            Self::RingCt(_) | Self::Data(_) => None,
+        }
+    }
+
+    /// Encodes this output without its type byte. Range proofs are witness data and are replaced
+    /// by an empty vector if `witness` is false, like Ghost Core does when computing the txid.
+    fn encode_inner<W: io::Write + ?Sized>(
+        &self,
+        w: &mut W,
+        witness: bool,
+    ) -> Result<usize, io::Error> {
+        let empty = Vec::new();
+        let mut len = 0;
+
+        match self {
+            Self::Standard(out) => {
+                len += out.value.to_sat().consensus_encode(w)?;
+                len += out.script_pubkey.consensus_encode(w)?;
+            }
+            Self::Ct(out) => {
+                len += out.commitment.consensus_encode(w)?;
+                len += out.data.consensus_encode(w)?;
+                len += out.script_pubkey.consensus_encode(w)?;
+                len += if witness { &out.range_proof } else { &empty }.consensus_encode(w)?;
+            }
+            Self::RingCt(out) => {
+                len += out.pubkey.consensus_encode(w)?;
+                len += out.commitment.consensus_encode(w)?;
+                len += out.data.consensus_encode(w)?;
+                len += if witness { &out.range_proof } else { &empty }.consensus_encode(w)?;
+            }
+            Self::Data(out) => {
+                len += out.data.consensus_encode(w)?;
+            }
+        }
+
+        Ok(len)
+    }
+
+    fn encode<W: io::Write + ?Sized>(&self, w: &mut W, witness: bool) -> Result<usize, io::Error> {
+        Ok(self.output_type().consensus_encode(w)? + self.encode_inner(w, witness)?)
+    }
+}
+
+impl Encodable for TxOutput {
+    #[inline]
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.encode(w, true)
+    }
+}
+
+impl Decodable for TxOutput {
+    fn consensus_decode_from_finite_reader<R: io::Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        Ok(match u8::consensus_decode_from_finite_reader(r)? {
+            Self::TYPE_STANDARD => Self::Standard(OutputStandard {
+                value: Amount::from_sat(Decodable::consensus_decode_from_finite_reader(r)?),
+                script_pubkey: Decodable::consensus_decode_from_finite_reader(r)?,
+            }),
+            Self::TYPE_CT => Self::Ct(OutputCT {
+                commitment: Decodable::consensus_decode_from_finite_reader(r)?,
+                data: Decodable::consensus_decode_from_finite_reader(r)?,
+                script_pubkey: Decodable::consensus_decode_from_finite_reader(r)?,
+                range_proof: Decodable::consensus_decode_from_finite_reader(r)?,
+            }),
+            Self::TYPE_RINGCT => Self::RingCt(OutputRingCT {
+                pubkey: Decodable::consensus_decode_from_finite_reader(r)?,
+                commitment: Decodable::consensus_decode_from_finite_reader(r)?,
+                data: Decodable::consensus_decode_from_finite_reader(r)?,
+                range_proof: Decodable::consensus_decode_from_finite_reader(r)?,
+            }),
+            Self::TYPE_DATA => Self::Data(OutputData {
+                data: Decodable::consensus_decode_from_finite_reader(r)?,
+            }),
+            _ => {
+                return Err(encode::Error::ParseFailed(
+                    "unknown transaction output type",
+                ))
+            }
+        })
+    }
+}
+
+impl GhostTransaction {
+    pub const TYPE_STANDARD: u8 = 0;
+    pub const TYPE_COINBASE: u8 = 1;
+    pub const TYPE_COINSTAKE: u8 = 2;
+
+    /// Returns true if this is a coinbase transaction.
+    #[inline]
+    pub fn is_coinbase(&self) -> bool {
+        self.tx_type == Self::TYPE_COINBASE
+    }
+
+    /// Returns true if this is a coinstake transaction (the first transaction of a proof-of-stake
+    /// block).
+    #[inline]
+    pub fn is_coinstake(&self) -> bool {
+        self.tx_type == Self::TYPE_COINSTAKE
+    }
+
+    /// Returns the height of the block this is the coinbase or coinstake transaction of. A
+    /// coinstake transaction stores it in the first 4 bytes of its first output, a data output. A
+    /// coinbase transaction stores it as the first push of its input's script (BIP34).
+    pub fn height(&self) -> Option<u32> {
+        if self.is_coinstake() {
+            match self.output.first()? {
+                TxOutput::Data(out) => {
+                    Some(u32::from_le_bytes(out.data.get(..4)?.try_into().ok()?))
+                }
+                _ => None,
+            }
+        } else if self.is_coinbase() {
+            let script_sig = &self.input.first()?.script_sig;
+            match script_sig.instructions_minimal().next()?.ok()? {
+                Instruction::PushBytes(push) => {
+                    read_scriptint(push.as_bytes()).ok()?.try_into().ok()
+                }
+                Instruction::Op(_) => None,
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Computes the txid of this [`GhostTransaction`]. Like in Bitcoin, the txid does not commit
+    /// to witness data, for Ghost this also excludes range proofs.
+    pub fn txid(&self) -> Txid {
+        let mut engine = Txid::engine();
+        self.encode(&mut engine, false)
+            .expect("engines don't error");
+        Txid::from_engine(engine)
+    }
+
+    /// Computes the wtxid of this [`GhostTransaction`], the hash of the full serialization.
+    pub fn wtxid(&self) -> Wtxid {
+        let mut engine = Wtxid::engine();
+        self.encode(&mut engine, true).expect("engines don't error");
+        Wtxid::from_engine(engine)
+    }
+
+    fn encode<W: io::Write + ?Sized>(&self, w: &mut W, witness: bool) -> Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.version.consensus_encode(w)?;
+        len += self.tx_type.consensus_encode(w)?;
+        len += self.lock_time.consensus_encode(w)?;
+        len += self.input.consensus_encode(w)?;
+        len += encode::VarInt(self.output.len() as u64).consensus_encode(w)?;
+        for output in &self.output {
+            len += output.encode(w, witness)?;
+        }
+        if witness {
+            for input in &self.input {
+                len += input.witness.consensus_encode(w)?;
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl Encodable for GhostTransaction {
+    #[inline]
+    fn consensus_encode<W: io::Write + ?Sized>(&self, w: &mut W) -> Result<usize, io::Error> {
+        self.encode(w, true)
+    }
+}
+
+impl Decodable for GhostTransaction {
+    fn consensus_decode_from_finite_reader<R: io::Read + ?Sized>(
+        r: &mut R,
+    ) -> Result<Self, encode::Error> {
+        let version = u8::consensus_decode_from_finite_reader(r)?;
+        if version < GHOST_TX_VERSION {
+            return Err(encode::Error::ParseFailed(
+                "transaction version is not a Ghost transaction version",
+            ));
+        }
+        let tx_type = Decodable::consensus_decode_from_finite_reader(r)?;
+        let lock_time = Decodable::consensus_decode_from_finite_reader(r)?;
+        let mut input: Vec<TxIn> = Decodable::consensus_decode_from_finite_reader(r)?;
+
+        let output_len = encode::VarInt::consensus_decode_from_finite_reader(r)?.0;
+        let mut output = Vec::new();
+        for _ in 0..output_len {
+            output.push(TxOutput::consensus_decode_from_finite_reader(r)?);
+        }
+
+        for input in &mut input {
+            input.witness = Witness::consensus_decode_from_finite_reader(r)?;
+        }
+
+        Ok(Self {
+            version,
+            tx_type,
+            lock_time,
+            input,
+            output,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{GhostTransaction, OutputCT, OutputData, OutputRingCT, OutputStandard, TxOutput};
+    use bitcoin::{
+        absolute::LockTime,
+        consensus::{deserialize, serialize},
+        hashes::{sha256d, Hash},
+        script::Builder,
+        Amount, OutPoint, ScriptBuf, Sequence, TxIn, Txid, Witness,
+    };
+
+    /// A coinstake-like transaction using every output type.
+    pub(crate) fn sample_transaction() -> GhostTransaction {
+        GhostTransaction {
+            version: 0xa0,
+            tx_type: GhostTransaction::TYPE_COINSTAKE,
+            lock_time: LockTime::from_consensus(100),
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array([7; 32]), 1),
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::from_slice(&[vec![1, 2, 3], vec![4; 33]]),
+            }],
+            output: vec![
+                TxOutput::Data(OutputData {
+                    data: vec![0x64, 0x00, 0x00, 0x00],
+                }),
+                TxOutput::Standard(OutputStandard {
+                    value: Amount::from_sat(123_456_789),
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x76, 0xa9, 0x14]),
+                }),
+                TxOutput::Ct(OutputCT {
+                    commitment: [8; 33],
+                    data: vec![9; 33],
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+                    range_proof: vec![10; 100],
+                }),
+                TxOutput::RingCt(OutputRingCT {
+                    pubkey: [2; 33],
+                    commitment: [9; 33],
+                    data: vec![],
+                    range_proof: vec![11; 50],
+                }),
+            ],
+        }
+    }
+
+    #[test]
+    fn serialization() {
+        let tx = sample_transaction();
+
+        let bytes = serialize(&tx);
+        assert_eq!(&bytes[..2], &[0xa0, 0x02]);
+        assert_eq!(deserialize::<GhostTransaction>(&bytes).unwrap(), tx);
+
+        // range proofs and witnesses are not part of the txid
+        let mut stripped = tx.clone();
+        stripped.input[0].witness = Witness::new();
+        if let TxOutput::Ct(out) = &mut stripped.output[2] {
+            out.range_proof.clear();
+        }
+        assert_eq!(stripped.txid(), tx.txid());
+        assert_ne!(stripped.wtxid(), tx.wtxid());
+        assert_eq!(
+            tx.wtxid().to_byte_array(),
+            sha256d::Hash::hash(&bytes).to_byte_array()
+        );
+    }
+
+    #[test]
+    fn height() {
+        let mut tx = sample_transaction();
+        assert_eq!(tx.height(), Some(100));
+
+        tx.tx_type = GhostTransaction::TYPE_STANDARD;
+        assert_eq!(tx.height(), None);
+
+        tx.tx_type = GhostTransaction::TYPE_COINBASE;
+        tx.input[0].script_sig = Builder::new().push_int(1_234_567).into_script();
+        assert_eq!(tx.height(), Some(1_234_567));
+
+        tx.input[0].script_sig = Builder::new().push_int(-1).into_script();
+        assert_eq!(tx.height(), None);
+    }
+
+    #[test]
+    fn deserialization_errors() {
+        let mut bytes = serialize(&sample_transaction());
+
+        // bitcoin style version
+        assert!(deserialize::<GhostTransaction>(&[0x02, 0x00, 0x00, 0x00]).is_err());
+
+        // trailing data
+        bytes.push(0);
+        assert!(deserialize::<GhostTransaction>(&bytes).is_err());
+
+        // unknown output type, the last output is a data output of 6 bytes followed by an empty
+        // witness
+        let mut tx = sample_transaction();
+        tx.input[0].witness = Witness::new();
+        tx.output.truncate(1);
+        let mut bytes = serialize(&tx);
+        let output_type_index = bytes.len() - 1 - 6;
+        assert_eq!(bytes[output_type_index], TxOutput::TYPE_DATA);
+        bytes[output_type_index] = 0;
+        assert!(deserialize::<GhostTransaction>(&bytes).is_err());
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{error::Result, received::Received, topic::Topic};
+use core::{fmt, time::Duration};
+use std::{
+    collections::VecDeque,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Instant,
+};
+
+/// An item produced by [`Watched`], or an event produced by a [`Watchdog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    Message(Received),
+    /// No message of a watched topic was received from `endpoint` since `since` (when the last
+    /// one was received, or when the [`Watchdog`] was created), which is longer ago than the
+    /// window. `silent_for` is the time between `since` and the check that produced this event.
+    Stale {
+        endpoint: String,
+        topic: Topic,
+        since: Instant,
+        silent_for: Duration,
+    },
+    /// A message of a watched topic was received again from a stale endpoint.
+    Recovered {
+        endpoint: String,
+        topic: Topic,
+    },
+}
+
+impl fmt::Display for WatchdogEvent {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Message(received) => write!(f, "Message({received})"),
+            Self::Stale {
+                endpoint,
+                topic,
+                silent_for,
+                ..
+            } => write!(f, "Stale({endpoint}, topic={topic}, for {silent_for:?})"),
+            Self::Recovered { endpoint, topic } => {
+                write!(f, "Recovered({endpoint}, topic={topic})")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    endpoint: String,
+    topic: Topic,
+    last: Instant,
+    stale: bool,
+}
+
+/// Detects publishers that stopped sending messages. A ZMQ socket stays connected while the node
+/// is stuck or its ZMQ notifications are misconfigured. The watchdog notices this by keeping the
+/// time the last message of every watched topic was received from every endpoint.
+///
+/// By default only the `hashblock` topic is watched. Ghost targets one block every 2 minutes, but
+/// the time between blocks varies, so choose a window of several times that to avoid false alarms.
+///
+/// A dead connection (for example, because the node's host went down) is only noticed by ZMQ when
+/// ZMTP heartbeats are enabled, see [`SubscriberBuilder::heartbeat_interval`]. Combining both
+/// detects a lost connection within seconds and a silent node within the window.
+///
+/// ```no_run
+/// use core::time::Duration;
+/// use ghostcore_zmq::{SubscriberBuilder, WatchdogEvent};
+///
+/// let watched = SubscriberBuilder::new(&["tcp://127.0.0.1:28332"])
+///     .heartbeat_interval(Duration::from_secs(10))
+///     .heartbeat_timeout(Duration::from_secs(30))
+///     .subscribe_watchdog(Duration::from_secs(20 * 60))
+///     .unwrap();
+///
+/// for event in watched {
+///     match event {
+///         Ok(WatchdogEvent::Message(received)) => println!("Received message: {received}"),
+///         Ok(event) => println!("{event}"),
+///         Err(err) => println!("Error receiving message: {err}"),
+///     }
+/// }
+/// ```
+///
+/// [`SubscriberBuilder::heartbeat_interval`]: crate::SubscriberBuilder::heartbeat_interval
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    endpoints: Vec<String>,
+    topics: Vec<Topic>,
+    window: Duration,
+    start: Instant,
+    entries: Vec<Entry>,
+}
+
+impl Watchdog {
+    /// Creates a new [`Watchdog`] that reports an endpoint as stale when no `hashblock` message
+    /// was received from it within `window`.
+    pub fn new(endpoints: &[&str], window: Duration) -> Self {
+        Self::new_at(endpoints, window, Instant::now())
+    }
+
+    /// Like [`Watchdog::new`], but with the time to start counting from.
+    pub fn new_at(endpoints: &[&str], window: Duration, now: Instant) -> Self {
+        Self {
+            endpoints: endpoints.iter().map(|&e| e.to_owned()).collect(),
+            topics: Vec::new(),
+            window,
+            start: now,
+            entries: Vec::new(),
+        }
+        .topics(&[Topic::HashBlock])
+    }
+
+    /// Sets the topics to watch. Every topic is expected to be received from every endpoint
+    /// within the window, so only watch topics that are published regularly. This forgets when
+    /// messages were received.
+    pub fn topics(mut self, topics: &[Topic]) -> Self {
+        let start = self.start;
+
+        self.topics = topics.to_vec();
+        self.entries = self
+            .endpoints
+            .iter()
+            .flat_map(|endpoint| {
+                topics.iter().map(move |&topic| Entry {
+                    endpoint: endpoint.clone(),
+                    topic,
+                    last: start,
+                    stale: false,
+                })
+            })
+            .collect();
+        self
+    }
+
+    /// Returns the watched topics.
+    #[inline]
+    pub fn watched_topics(&self) -> &[Topic] {
+        &self.topics
+    }
+
+    /// Returns the window after which an endpoint is reported as stale.
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// Returns when the last message of `topic` was received from `endpoint` (or when the
+    /// watchdog was created), or [`None`] if the endpoint or topic is not watched.
+    pub fn last_received(&self, endpoint: &str, topic: Topic) -> Option<Instant> {
+        self.entry(endpoint, topic).map(|entry| entry.last)
+    }
+
+    /// Returns true if `endpoint` is stale for `topic`.
+    pub fn is_stale(&self, endpoint: &str, topic: Topic) -> bool {
+        self.entry(endpoint, topic).is_some_and(|entry| entry.stale)
+    }
+
+    fn entry(&self, endpoint: &str, topic: Topic) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.endpoint == endpoint && entry.topic == topic)
+    }
+
+    /// Records a received message. Returns [`WatchdogEvent::Recovered`] if its endpoint was stale
+    /// for its topic.
+    #[inline]
+    pub fn push(&mut self, received: &Received) -> Option<WatchdogEvent> {
+        self.push_at(received, Instant::now())
+    }
+
+    /// Like [`Watchdog::push`], but with the time the message was received.
+    pub fn push_at(&mut self, received: &Received, now: Instant) -> Option<WatchdogEvent> {
+        let topic = received.message.topic_type();
+
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.endpoint == received.endpoint && entry.topic == topic)?;
+
+        entry.last = now;
+
+        if entry.stale {
+            entry.stale = false;
+            Some(WatchdogEvent::Recovered {
+                endpoint: entry.endpoint.clone(),
+                topic,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a [`WatchdogEvent::Stale`] for every endpoint and topic that became stale. Every
+    /// endpoint and topic is only reported once, until it recovers.
+    #[inline]
+    pub fn check(&mut self) -> Vec<WatchdogEvent> {
+        self.check_at(Instant::now())
+    }
+
+    /// Like [`Watchdog::check`], but with the current time.
+    pub fn check_at(&mut self, now: Instant) -> Vec<WatchdogEvent> {
+        let window = self.window;
+
+        self.entries
+            .iter_mut()
+            .filter(|entry| !entry.stale && now.saturating_duration_since(entry.last) >= window)
+            .map(|entry| {
+                entry.stale = true;
+                WatchdogEvent::Stale {
+                    endpoint: entry.endpoint.clone(),
+                    topic: entry.topic,
+                    since: entry.last,
+                    silent_for: now.saturating_duration_since(entry.last),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the earliest time [`Watchdog::check_at`] may report a stale endpoint, or [`None`]
+    /// if every endpoint is stale already.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.entries
+            .iter()
+            .filter(|entry| !entry.stale)
+            .map(|entry| entry.last + self.window)
+            .min()
+    }
+}
+
+/// Wraps a [`Receiver`] of messages tagged with their endpoint (like the one returned by
+/// [`subscribe_receiver_with_source`][crate::subscribe_receiver_with_source]), passing every
+/// message to a [`Watchdog`]. Produces the messages and the watchdog's events, even when no
+/// messages are received.
+#[derive(Debug)]
+pub struct Watched {
+    rx: Receiver<Result<Received>>,
+    watchdog: Watchdog,
+    pending: VecDeque<WatchdogEvent>,
+}
+
+impl Watched {
+    /// Wraps `rx` using `watchdog`.
+    #[inline]
+    pub fn new(rx: Receiver<Result<Received>>, watchdog: Watchdog) -> Self {
+        Self {
+            rx,
+            watchdog,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns a reference to the [`Watchdog`] used.
+    #[inline]
+    pub fn watchdog(&self) -> &Watchdog {
+        &self.watchdog
+    }
+
+    /// Returns the wrapped [`Receiver`].
+    #[inline]
+    pub fn into_inner(self) -> Receiver<Result<Received>> {
+        self.rx
+    }
+}
+
+impl Iterator for Watched {
+    type Item = Result<WatchdogEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            let now = Instant::now();
+            self.pending.extend(self.watchdog.check_at(now));
+            if !self.pending.is_empty() {
+                continue;
+            }
+
+            let msg = match self.watchdog.next_deadline() {
+                Some(deadline) => match self.rx.recv_timeout(deadline - now) {
+                    Ok(msg) => msg,
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    Err(RecvTimeoutError::Disconnected) => return None,
+                },
+                None => self.rx.recv().ok()?,
+            };
+
+            match msg {
+                Ok(received) => {
+                    self.pending.extend(self.watchdog.push(&received));
+                    self.pending.push_back(WatchdogEvent::Message(received));
+                }
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{adapter::tests::received, Message, Topic, Watchdog, WatchdogEvent, Watched};
+    use bitcoin::{hashes::Hash, BlockHash, Txid};
+    use core::time::Duration;
+    use std::{sync::mpsc::channel, time::Instant};
+
+    #[test]
+    fn watchdog() {
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        let mut watchdog =
+            Watchdog::new_at(&["tcp://a", "tcp://b"], Duration::from_secs(10), start);
+        let block = Message::HashBlock(BlockHash::all_zeros(), 0);
+        let tx = Message::HashTx(Txid::all_zeros(), 0);
+
+        assert_eq!(
+            watchdog.push_at(&received("tcp://a", block.clone()), at(5)),
+            None
+        );
+        // not watched
+        assert_eq!(watchdog.push_at(&received("tcp://b", tx), at(6)), None);
+        assert_eq!(watchdog.next_deadline(), Some(at(10)));
+
+        let stale = watchdog.check_at(at(10));
+        assert_eq!(
+            stale,
+            [WatchdogEvent::Stale {
+                endpoint: "tcp://b".to_owned(),
+                topic: Topic::HashBlock,
+                since: start,
+                silent_for: Duration::from_secs(10),
+            }]
+        );
+        assert_eq!(
+            stale[0].to_string(),
+            "Stale(tcp://b, topic=hashblock, for 10s)"
+        );
+        // only reported once
+        assert_eq!(watchdog.check_at(at(11)), []);
+        assert!(watchdog.is_stale("tcp://b", Topic::HashBlock));
+        assert_eq!(watchdog.next_deadline(), Some(at(15)));
+
+        assert_eq!(
+            watchdog.push_at(&received("tcp://b", block), at(12)),
+            Some(WatchdogEvent::Recovered {
+                endpoint: "tcp://b".to_owned(),
+                topic: Topic::HashBlock
+            })
+        );
+        assert_eq!(
+            watchdog.last_received("tcp://b", Topic::HashBlock),
+            Some(at(12))
+        );
+        assert_eq!(watchdog.check_at(at(14)), []);
+    }
+
+    #[test]
+    fn watched() {
+        let (tx, rx) = channel();
+        let block = received("tcp://a", Message::HashBlock(BlockHash::all_zeros(), 0));
+        tx.send(Ok(block.clone())).unwrap();
+
+        let mut watched = Watched::new(rx, Watchdog::new(&["tcp://a"], Duration::from_millis(20)));
+
+        assert_eq!(
+            watched.next().unwrap().unwrap(),
+            WatchdogEvent::Message(block)
+        );
+        // nothing is received, the receiver times out
+        assert!(matches!(
+            watched.next().unwrap().unwrap(),
+            WatchdogEvent::Stale {
+                topic: Topic::HashBlock,
+                ..
+            }
+        ));
+
+        drop(tx);
+        assert!(watched.next().is_none());
+    }
+}